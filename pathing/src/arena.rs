@@ -0,0 +1,158 @@
+// Scratch memory of the searches that is kept between queries
+// Every node slot carries the generation of the search that last wrote it, so starting a new search is a
+// counter increment instead of allocating and clearing arrays the size of the map
+// There is one arena per thread, which covers the batch searches on the rayon pool as well
+
+use std::cell::RefCell;
+use std::collections::BinaryHeap;
+
+use movingai::Coords2D;
+
+use crate::jps::JumpNode;
+use crate::pathfinding::OpenNode;
+
+/// Best known way to every node of one search direction
+#[derive(Default)]
+pub struct NodeTable {
+    generation: u32,
+    /// Generation in which g and parent of the node were last written
+    stamps: Vec<u32>,
+    /// Generation in which the node was closed
+    closed: Vec<u32>,
+    g_score: Vec<f64>,
+    parent: Vec<usize>,
+}
+
+impl NodeTable {
+    /// Forgets all nodes and makes room for 'size' of them
+    pub fn begin(&mut self, size: usize) {
+        if self.stamps.len() < size {
+            self.stamps.resize(size, 0);
+            self.closed.resize(size, 0);
+            self.g_score.resize(size, f64::INFINITY);
+            self.parent.resize(size, usize::MAX);
+        }
+        if self.generation == u32::MAX {
+            // Old stamps could match again after the wrap around
+            self.stamps.iter_mut().for_each(|stamp| *stamp = 0);
+            self.closed.iter_mut().for_each(|stamp| *stamp = 0);
+            self.generation = 0;
+        }
+        self.generation += 1;
+    }
+
+    /// Cost of the best known way to the node, infinite if it was not reached yet
+    pub fn g(&self, index: usize) -> f64 {
+        if self.stamps[index] == self.generation {
+            self.g_score[index]
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Node the best known way comes from, usize::MAX if there is none
+    pub fn parent(&self, index: usize) -> usize {
+        if self.stamps[index] == self.generation {
+            self.parent[index]
+        } else {
+            usize::MAX
+        }
+    }
+
+    pub fn update(&mut self, index: usize, g: f64, parent: usize) {
+        self.stamps[index] = self.generation;
+        self.g_score[index] = g;
+        self.parent[index] = parent;
+    }
+
+    pub fn is_closed(&self, index: usize) -> bool {
+        self.closed[index] == self.generation
+    }
+
+    pub fn close(&mut self, index: usize) {
+        self.closed[index] = self.generation;
+    }
+}
+
+/// Work done by the searches since the last reset, read back by RustPixelMap for the search statistics
+/// The counters are kept across 'begin', so queries that run several searches report all of them
+#[derive(Default)]
+pub struct SearchCounters {
+    pub expanded: usize,
+    pub generated: usize,
+    pub open_peak: usize,
+    /// Expanded tiles in order, only recorded while 'tracing' is set
+    pub tracing: bool,
+    pub trace: Vec<Coords2D>,
+}
+
+impl SearchCounters {
+    pub fn new(tracing: bool) -> Self {
+        SearchCounters {
+            tracing,
+            ..SearchCounters::default()
+        }
+    }
+
+    /// A node was taken from the open list
+    pub fn expand(&mut self, tile: Coords2D) {
+        self.expanded += 1;
+        if self.tracing {
+            self.trace.push(tile);
+        }
+    }
+
+    /// A node was pushed to an open list that now holds 'open_len' nodes
+    pub fn generate(&mut self, open_len: usize) {
+        self.generated += 1;
+        self.open_peak = self.open_peak.max(open_len);
+    }
+
+    /// Adds the counts of a search that kept its own counters
+    pub fn merge(&mut self, other: SearchCounters) {
+        self.expanded += other.expanded;
+        self.generated += other.generated;
+        self.open_peak = self.open_peak.max(other.open_peak);
+        self.trace.extend(other.trace);
+    }
+}
+
+#[derive(Default)]
+pub struct SearchArena {
+    pub nodes: NodeTable,
+    /// Nodes of the search from the goal, only used by the bidirectional search
+    pub reverse: NodeTable,
+    /// Open lists and buffers, empty at the start of every search but with the capacity of earlier searches
+    pub open: BinaryHeap<OpenNode>,
+    pub reverse_open: BinaryHeap<OpenNode>,
+    pub jumps: BinaryHeap<JumpNode>,
+    pub jump_points: Vec<JumpNode>,
+    pub successors: Vec<(Coords2D, f64)>,
+    pub counters: SearchCounters,
+}
+
+impl SearchArena {
+    /// Starts a new search over 'size' nodes, all nodes are unvisited afterwards
+    /// The reverse table is left alone, searches that use it start it themselves
+    pub fn begin(&mut self, size: usize) {
+        self.nodes.begin(size);
+        self.open.clear();
+        self.reverse_open.clear();
+        self.jumps.clear();
+        self.jump_points.clear();
+        self.successors.clear();
+    }
+}
+
+thread_local! {
+    static ARENA: RefCell<SearchArena> = RefCell::new(SearchArena::default());
+}
+
+/// Runs the search with the arena of the current thread
+/// A search that is started from inside of another one gets a fresh arena
+pub fn with_arena<R, F: FnOnce(&mut SearchArena) -> R>(search: F) -> R {
+    ARENA.with(|arena| match arena.try_borrow_mut() {
+        Ok(mut arena) => search(&mut arena),
+        Err(_) => search(&mut SearchArena::default()),
+    })
+}
@@ -0,0 +1,241 @@
+// Connected areas of walkable tiles, e.g. island expansions that ground units can not reach
+// Tiles are connected through their 4 direct neighbors, which matches every search that does not cut corners
+// A diagonal step with CornerCutting::Single always has a walkable orthogonal tile, so it is connected the same
+// way, only CornerCutting::Always and JPS can pass between two walls into an area of another label
+// Labels are cached so reachability queries are a single comparison
+// Changes of the map only touch the areas around the changed tiles, areas elsewhere keep their labels
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use movingai::{Coords2D, Map2D};
+
+/// Label of tiles that are not walkable
+pub const WALL: i32 = -1;
+
+#[derive(Clone, Debug)]
+pub struct Components {
+    width: usize,
+    height: usize,
+    /// Union-find parents, every tile points at itself for the root of its set, walls are sets of their own
+    parent: Vec<usize>,
+    /// Number of tiles below every root, keeps the trees flat
+    size: Vec<usize>,
+    /// Component number of every root, WALL for walls, other tiles use the label of their root
+    root_labels: Vec<i32>,
+    /// Labels of areas that were merged or blocked, handed out again before new labels
+    free_labels: Vec<i32>,
+    count: usize,
+}
+
+impl Components {
+    /// Areas are numbered in row-major order of their first tile
+    pub fn new<U, T: Map2D<U>>(map: &T) -> Self {
+        let (width, height) = (map.width(), map.height());
+        let mut components = Components {
+            width,
+            height,
+            parent: (0..width * height).collect(),
+            size: vec![1; width * height],
+            root_labels: vec![WALL; width * height],
+            free_labels: vec![],
+            count: 0,
+        };
+        for y in 0..height {
+            for x in 0..width {
+                if !map.is_traversable((x, y)) {
+                    continue;
+                }
+                if x + 1 < width && map.is_traversable((x + 1, y)) {
+                    components.union(y * width + x, y * width + x + 1);
+                }
+                if y + 1 < height && map.is_traversable((x, y + 1)) {
+                    components.union(y * width + x, (y + 1) * width + x);
+                }
+            }
+        }
+        for index in 0..width * height {
+            let root = components.find(index);
+            if map.is_traversable((index % width, index / width))
+                && components.root_labels[root] == WALL
+            {
+                components.root_labels[root] = components.count as i32;
+                components.count += 1;
+            }
+        }
+        components
+    }
+
+    /// Merges the components around tiles that just became walkable
+    /// Every opened tile starts as an area of its own and is joined with its walkable neighbors
+    pub fn open<U, T: Map2D<U>, I: IntoIterator<Item = Coords2D>>(&mut self, map: &T, tiles: I) {
+        let opened: Vec<usize> = tiles
+            .into_iter()
+            .filter(|&tile| map.is_traversable(tile))
+            .map(|(x, y)| y * self.width + x)
+            .filter(|&index| self.label(index) == WALL)
+            .collect();
+        for &index in opened.iter() {
+            self.root_labels[index] = self.new_label();
+        }
+        for index in opened {
+            for neighbor in self.neighbors(map, index) {
+                self.union(index, neighbor);
+            }
+        }
+    }
+
+    /// Splits the components around tiles that just became walls
+    /// Union-find can not undo a union, so only the areas that contained the blocked tiles are flooded again
+    /// The first part of every split area keeps its label
+    pub fn block<U, T: Map2D<U>, I: IntoIterator<Item = Coords2D>>(&mut self, map: &T, tiles: I) {
+        let blocked: Vec<usize> = tiles
+            .into_iter()
+            .filter(|&tile| !map.is_traversable(tile))
+            .map(|(x, y)| y * self.width + x)
+            .filter(|&index| self.label(index) != WALL)
+            .collect();
+        // Label of every split area and whether one of its parts already took it over
+        let mut old_labels: HashMap<usize, (i32, bool)> = blocked
+            .iter()
+            .map(|&index| (self.find(index), (self.label(index), false)))
+            .collect();
+
+        // All remaining tiles of a split area are connected to a walkable neighbor of a blocked tile
+        let mut visited = HashSet::new();
+        let mut parts = vec![];
+        for &index in blocked.iter() {
+            for seed in self.neighbors(map, index) {
+                if !visited.insert(seed) {
+                    continue;
+                }
+                let mut part = vec![seed];
+                let mut queue: VecDeque<usize> = vec![seed].into();
+                while let Some(current) = queue.pop_front() {
+                    for next in self.neighbors(map, current) {
+                        if visited.insert(next) {
+                            part.push(next);
+                            queue.push_back(next);
+                        }
+                    }
+                }
+                parts.push((self.find(seed), part));
+            }
+        }
+
+        for &index in blocked.iter() {
+            self.parent[index] = index;
+            self.size[index] = 1;
+            self.root_labels[index] = WALL;
+        }
+        for (old_root, part) in parts {
+            let root = part[0];
+            for &index in part.iter() {
+                self.parent[index] = root;
+            }
+            self.size[root] = part.len();
+            let (label, taken) = old_labels.get_mut(&old_root).unwrap();
+            self.root_labels[root] = if *taken {
+                self.new_label()
+            } else {
+                *taken = true;
+                *label
+            };
+        }
+        for (_, (label, taken)) in old_labels.drain() {
+            if !taken {
+                self.free_labels.push(label);
+                self.count -= 1;
+            }
+        }
+    }
+
+    /// Walkable tiles next to a tile
+    fn neighbors<U, T: Map2D<U>>(&self, map: &T, index: usize) -> Vec<usize> {
+        let (x, y) = (index % self.width, index / self.width);
+        let mut neighbors = Vec::with_capacity(4);
+        if x > 0 {
+            neighbors.push((x - 1, y));
+        }
+        if y > 0 {
+            neighbors.push((x, y - 1));
+        }
+        if x + 1 < self.width {
+            neighbors.push((x + 1, y));
+        }
+        if y + 1 < self.height {
+            neighbors.push((x, y + 1));
+        }
+        neighbors
+            .into_iter()
+            .filter(|&neighbor| map.is_traversable(neighbor))
+            .map(|(x, y)| y * self.width + x)
+            .collect()
+    }
+
+    fn new_label(&mut self) -> i32 {
+        let label = match self.free_labels.pop() {
+            Some(label) => label,
+            None => (self.count + self.free_labels.len()) as i32,
+        };
+        self.count += 1;
+        label
+    }
+
+    fn find(&self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            index = self.parent[index];
+        }
+        index
+    }
+
+    /// Joins two areas, the merged area keeps the lower of their labels
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        let (label, other) = (self.root_labels[a], self.root_labels[b]);
+        if label != WALL && other != WALL {
+            self.root_labels[a] = label.min(other);
+            self.free_labels.push(label.max(other));
+            self.count -= 1;
+        }
+    }
+
+    fn label(&self, index: usize) -> i32 {
+        self.root_labels[self.find(index)]
+    }
+
+    /// Row-major labels of all tiles
+    pub fn labels(&self) -> Vec<i32> {
+        (0..self.parent.len())
+            .map(|index| self.label(index))
+            .collect()
+    }
+
+    /// Component of a tile inside of the map, None for walls
+    pub fn component_of(&self, tile: Coords2D) -> Option<usize> {
+        match self.label(tile.1 * self.width + tile.0) {
+            WALL => None,
+            label => Some(label as usize),
+        }
+    }
+
+    /// True if both tiles are walkable and connected through their 4 direct neighbors
+    /// Searches with CornerCutting::Always can also reach tiles for which this is false
+    pub fn is_reachable(&self, a: Coords2D, b: Coords2D) -> bool {
+        match (self.component_of(a), self.component_of(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
@@ -0,0 +1,151 @@
+// Optional weight layer on top of the walkable / blocked map
+// Every step costs its base distance multiplied by the weight of the tile that is entered,
+// which lets searches avoid areas like enemy threat zones without treating them as walls
+
+use std::str::FromStr;
+
+use movingai::Coords2D;
+
+/// How the added weight of a circle decreases from its center to its edge
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Falloff {
+    /// Full weight everywhere inside the circle
+    Constant,
+    Linear,
+    Quadratic,
+}
+
+impl Falloff {
+    /// Fraction of the weight that is applied at 'distance' from the center, 0 outside of the circle
+    pub fn factor(self, distance: f64, radius: f64) -> f64 {
+        if distance > radius {
+            return 0.0;
+        }
+        let remaining = if radius > 0.0 {
+            1.0 - distance / radius
+        } else {
+            1.0
+        };
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => remaining,
+            Falloff::Quadratic => remaining * remaining,
+        }
+    }
+}
+
+impl FromStr for Falloff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "constant" => Ok(Falloff::Constant),
+            "linear" => Ok(Falloff::Linear),
+            "quadratic" => Ok(Falloff::Quadratic),
+            _ => Err(format!(
+                "Unknown falloff '{}', expected one of: constant, linear, quadratic",
+                s
+            )),
+        }
+    }
+}
+
+/// Row-major step weights, all weights are at least 1 so the heuristics stay admissible
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostGrid {
+    width: usize,
+    height: usize,
+    weights: Vec<f64>,
+}
+
+impl CostGrid {
+    pub const MIN_WEIGHT: f64 = 1.0;
+
+    /// Every tile has the neutral weight of 1
+    pub fn new(width: usize, height: usize) -> Self {
+        CostGrid {
+            width,
+            height,
+            weights: vec![CostGrid::MIN_WEIGHT; width * height],
+        }
+    }
+
+    pub fn from_weights(width: usize, height: usize, weights: Vec<f64>) -> Result<Self, String> {
+        if weights.len() != width * height {
+            return Err(format!(
+                "Expected {} weights for a map of size {}x{}, got {}",
+                width * height,
+                width,
+                height,
+                weights.len()
+            ));
+        }
+        if let Some(weight) = weights.iter().find(|&&w| !CostGrid::is_valid_weight(w)) {
+            return Err(format!("Weights have to be at least 1, got {}", weight));
+        }
+        Ok(CostGrid {
+            width,
+            height,
+            weights,
+        })
+    }
+
+    /// Rejects NaN as well
+    fn is_valid_weight(weight: f64) -> bool {
+        weight >= CostGrid::MIN_WEIGHT
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// Weight of the tile at the row-major index
+    pub fn weight(&self, index: usize) -> f64 {
+        self.weights[index]
+    }
+
+    pub fn set_weight(&mut self, tile: Coords2D, weight: f64) -> Result<(), String> {
+        if !CostGrid::is_valid_weight(weight) {
+            return Err(format!("Weights have to be at least 1, got {}", weight));
+        }
+        let index = self.index(tile)?;
+        self.weights[index] = weight;
+        Ok(())
+    }
+
+    /// Adds 'weight' scaled by the falloff to every tile within 'radius' of 'center'
+    /// A negative weight removes a circle again, weights never drop below 1
+    pub fn add_circle(&mut self, center: (f64, f64), radius: f64, weight: f64, falloff: Falloff) {
+        if self.weights.is_empty() {
+            return;
+        }
+        let min_x = (center.0 - radius).floor().max(0.0) as usize;
+        let min_y = (center.1 - radius).floor().max(0.0) as usize;
+        let max_x =
+            ((center.0 + radius).ceil().max(0.0) as usize).min(self.width.saturating_sub(1));
+        let max_y =
+            ((center.1 + radius).ceil().max(0.0) as usize).min(self.height.saturating_sub(1));
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let distance =
+                    ((x as f64 - center.0).powi(2) + (y as f64 - center.1).powi(2)).sqrt();
+                let factor = falloff.factor(distance, radius);
+                if factor <= 0.0 {
+                    continue;
+                }
+                let cell = &mut self.weights[y * self.width + x];
+                *cell = (*cell + weight * factor).max(CostGrid::MIN_WEIGHT);
+            }
+        }
+    }
+
+    fn index(&self, tile: Coords2D) -> Result<usize, String> {
+        if tile.0 >= self.width || tile.1 >= self.height {
+            return Err(format!(
+                "Tile {:?} is outside of the map of size {}x{}",
+                tile, self.width, self.height
+            ));
+        }
+        Ok(tile.1 * self.width + tile.0)
+    }
+}
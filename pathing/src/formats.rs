@@ -0,0 +1,142 @@
+// Map and scenario files from the MovingAI benchmark sets (https://movingai.com/benchmarks/formats.html)
+// movingai's own parser panics on malformed files, these return an error message instead
+
+use movingai::SceneRecord;
+
+use crate::grid::is_walkable_tile;
+
+/// Grid of a '.map' file as width, height and row-major tiles
+/// Tiles that pass grid::is_walkable_tile become walkable '.', every other tile becomes the wall 'O'
+pub fn parse_map(contents: &str) -> Result<(usize, usize, Vec<char>), String> {
+    let mut lines = contents.lines().map(str::trim_end);
+    let (mut width, mut height) = (None, None);
+    for line in &mut lines {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("map"), None) => break,
+            (Some("height"), Some(value)) => height = value.parse::<usize>().ok(),
+            (Some("width"), Some(value)) => width = value.parse::<usize>().ok(),
+            _ => {}
+        }
+    }
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err("Map header needs a valid 'width' and 'height'".to_string()),
+    };
+    let rows: Vec<&str> = lines.filter(|line| !line.is_empty()).collect();
+    if rows.len() != height {
+        return Err(format!("Expected {} rows, got {}", height, rows.len()));
+    }
+    let mut tiles = Vec::with_capacity(width * height);
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(format!(
+                "Expected {} tiles in row {}, got {}",
+                width,
+                y,
+                row.chars().count()
+            ));
+        }
+        tiles.extend(
+            row.chars()
+                .map(|tile| if is_walkable_tile(tile) { '.' } else { 'O' }),
+        );
+    }
+    Ok((width, height, tiles))
+}
+
+/// Rows of a '.scen' file, the 'version' line is optional
+pub fn parse_scenarios(contents: &str) -> Result<Vec<SceneRecord>, String> {
+    let mut scenarios = vec![];
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with("version") {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 9 {
+            return Err(format!(
+                "Line {}: expected 9 tab separated fields, got {}",
+                number + 1,
+                fields.len()
+            ));
+        }
+        let error = |field: &str| format!("Line {}: invalid {}", number + 1, field);
+        let integer =
+            |index: usize, field: &str| fields[index].parse::<usize>().map_err(|_| error(field));
+        scenarios.push(SceneRecord {
+            bucket: fields[0].parse().map_err(|_| error("bucket"))?,
+            map_file: fields[1].to_string(),
+            map_width: integer(2, "map width")?,
+            map_height: integer(3, "map height")?,
+            start_pos: (integer(4, "start x")?, integer(5, "start y")?),
+            goal_pos: (integer(6, "goal x")?, integer(7, "goal y")?),
+            optimal_length: fields[8].parse().map_err(|_| error("optimal length"))?,
+        });
+    }
+    Ok(scenarios)
+}
+
+/// Grid of digits with one line per row like AutomatonLE.txt, as width, height and row-major values
+/// The first line is row 0, with 'flip_y' the last line is row 0 instead (SC2 counts y upwards)
+pub fn parse_grid(contents: &str, flip_y: bool) -> Result<(usize, usize, Vec<u8>), String> {
+    let mut rows: Vec<(usize, &str)> = contents
+        .lines()
+        .map(str::trim_end)
+        .enumerate()
+        .map(|(number, line)| (number + 1, line))
+        .collect();
+    while let Some((_, "")) = rows.last() {
+        rows.pop();
+    }
+    let width = match rows.first() {
+        Some((_, line)) => line.len(),
+        None => return Err("The grid is empty".to_string()),
+    };
+    if flip_y {
+        rows.reverse();
+    }
+    let mut values = Vec::with_capacity(width * rows.len());
+    for &(number, line) in rows.iter() {
+        if line.len() != width {
+            return Err(format!(
+                "Line {}: expected {} digits, got {}",
+                number,
+                width,
+                line.len()
+            ));
+        }
+        for (column, character) in line.chars().enumerate() {
+            match character.to_digit(10) {
+                Some(digit) => values.push(digit as u8),
+                None => {
+                    return Err(format!(
+                        "Line {}, column {}: expected a digit, got '{}'",
+                        number,
+                        column + 1,
+                        character
+                    ))
+                }
+            }
+        }
+    }
+    Ok((width, rows.len(), values))
+}
+
+/// Inverse of parse_grid, values have to be single digits
+pub fn write_grid(width: usize, values: &[u8], flip_y: bool) -> Result<String, String> {
+    if let Some(value) = values.iter().find(|&&value| value > 9) {
+        return Err(format!(
+            "Only values from 0 to 9 can be written, got {}",
+            value
+        ));
+    }
+    let mut rows: Vec<String> = values
+        .chunks(width.max(1))
+        .map(|row| row.iter().map(|value| char::from(b'0' + value)).collect())
+        .collect();
+    if flip_y {
+        rows.reverse();
+    }
+    Ok(rows.join("\n"))
+}
@@ -0,0 +1,329 @@
+// Walkability stored as one bit per tile, 32 times smaller than the chars of MovingAiMap
+// The bits can live in a memory-mapped file, so large sets of maps can be opened without reading them
+
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+
+use movingai::{Coords2D, CoordsIter, Map2D};
+
+const WALKABLE: char = '.';
+const WALL: char = 'O';
+
+/// Tiles of a MovingAI map that count as walkable, the same as MovingAiMap::is_traversable
+pub fn is_walkable_tile(tile: char) -> bool {
+    matches!(tile, '.' | 'G' | 'S' | 'W')
+}
+
+/// Row-major coordinates of every tile of a map, like Map2D::coords without building a MovingAiMap
+pub fn tiles<U, T: Map2D<U>>(map: &T) -> impl Iterator<Item = Coords2D> {
+    let (width, height) = (map.width(), map.height());
+    (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+}
+
+#[cfg(unix)]
+mod mapping {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    /// Read-only view of a whole file
+    pub struct MappedFile {
+        pointer: *mut libc::c_void,
+        len: usize,
+    }
+
+    // The mapping is never written to, so it can be read from any thread
+    unsafe impl Send for MappedFile {}
+    unsafe impl Sync for MappedFile {}
+
+    impl MappedFile {
+        pub fn open(file: &File) -> io::Result<Self> {
+            let len = file.metadata()?.len() as usize;
+            if len == 0 {
+                // Empty mappings are not allowed
+                return Ok(MappedFile {
+                    pointer: ptr::null_mut(),
+                    len,
+                });
+            }
+            let pointer = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if pointer == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(MappedFile { pointer, len })
+        }
+
+        pub fn bytes(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            unsafe { std::slice::from_raw_parts(self.pointer as *const u8, self.len) }
+        }
+
+        pub fn is_memory_mapped(&self) -> bool {
+            true
+        }
+    }
+
+    impl Drop for MappedFile {
+        fn drop(&mut self) {
+            if self.len > 0 {
+                unsafe {
+                    libc::munmap(self.pointer, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod mapping {
+    use std::fs::File;
+    use std::io::{self, Read};
+
+    /// Other platforms read the whole file instead
+    pub struct MappedFile {
+        bytes: Vec<u8>,
+    }
+
+    impl MappedFile {
+        pub fn open(mut file: &File) -> io::Result<Self> {
+            let mut bytes = vec![];
+            file.read_to_end(&mut bytes)?;
+            Ok(MappedFile { bytes })
+        }
+
+        pub fn bytes(&self) -> &[u8] {
+            &self.bytes
+        }
+
+        pub fn is_memory_mapped(&self) -> bool {
+            false
+        }
+    }
+}
+
+pub use mapping::MappedFile;
+
+impl MappedFile {
+    pub fn open_path(path: &str) -> io::Result<Self> {
+        MappedFile::open(&File::open(path)?)
+    }
+}
+
+enum Bits {
+    Owned(Vec<u8>),
+    /// The packed tiles are the given range of the file
+    Mapped(MappedFile, Range<usize>),
+}
+
+/// Row-major walkability, eight tiles per byte with the lowest bit first
+/// Behaves like a MovingAiMap whose walkable tiles are '.' and whose walls are 'O', which only connects
+/// tiles in the 4 straight directions in 'neighbors' and 'is_traversable_from'
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    bits: Bits,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize, walkable: &[bool]) -> Self {
+        let mut bytes = vec![0; (width * height).div_ceil(8)];
+        for (index, _) in walkable.iter().enumerate().filter(|(_, &free)| free) {
+            bytes[index / 8] |= 1 << (index % 8);
+        }
+        BitGrid {
+            width,
+            height,
+            bits: Bits::Owned(bytes),
+        }
+    }
+
+    /// Tiles of a MovingAI map, missing tiles are walls
+    pub fn from_tiles(width: usize, height: usize, tiles: &[char]) -> Self {
+        let walkable: Vec<bool> = (0..width * height)
+            .map(|index| tiles.get(index).is_some_and(|&tile| is_walkable_tile(tile)))
+            .collect();
+        BitGrid::new(width, height, &walkable)
+    }
+
+    pub fn from_bytes(width: usize, height: usize, bytes: Vec<u8>) -> Result<Self, String> {
+        BitGrid::check_len(width, height, bytes.len())?;
+        Ok(BitGrid {
+            width,
+            height,
+            bits: Bits::Owned(bytes),
+        })
+    }
+
+    /// Uses the bytes of the file in 'range' without copying them
+    pub fn mapped(
+        width: usize,
+        height: usize,
+        file: MappedFile,
+        range: Range<usize>,
+    ) -> Result<Self, String> {
+        BitGrid::check_len(width, height, range.len())?;
+        if range.end > file.bytes().len() {
+            return Err(format!(
+                "Tiles end at byte {}, the file has {} bytes",
+                range.end,
+                file.bytes().len()
+            ));
+        }
+        Ok(BitGrid {
+            width,
+            height,
+            bits: Bits::Mapped(file, range),
+        })
+    }
+
+    fn check_len(width: usize, height: usize, len: usize) -> Result<(), String> {
+        let expected = width
+            .checked_mul(height)
+            .ok_or_else(|| format!("Map size {}x{} is too large", width, height))?
+            .div_ceil(8);
+        if len != expected {
+            return Err(format!(
+                "Expected {} bytes for a map of size {}x{}, got {}",
+                expected, width, height, len
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match &self.bits {
+            Bits::Owned(bytes) => bytes,
+            Bits::Mapped(file, range) => &file.bytes()[range.clone()],
+        }
+    }
+
+    /// True while the tiles are read from a memory-mapped file, false on platforms that read the file instead
+    pub fn is_mapped(&self) -> bool {
+        match &self.bits {
+            Bits::Mapped(file, _) => file.is_memory_mapped(),
+            Bits::Owned(_) => false,
+        }
+    }
+
+    /// Changing a memory-mapped grid copies it into memory first, the file is never written
+    pub fn set(&mut self, (x, y): Coords2D, walkable: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        if let Bits::Mapped(..) = self.bits {
+            self.bits = Bits::Owned(self.bytes().to_vec());
+        }
+        if let Bits::Owned(bytes) = &mut self.bits {
+            let index = y * self.width + x;
+            if walkable {
+                bytes[index / 8] |= 1 << (index % 8);
+            } else {
+                bytes[index / 8] &= !(1 << (index % 8));
+            }
+        }
+    }
+
+    fn bit(&self, (x, y): Coords2D) -> bool {
+        let index = y * self.width + x;
+        self.bytes()[index / 8] & (1 << (index % 8)) != 0
+    }
+}
+
+impl Clone for BitGrid {
+    /// Copies are always held in memory
+    fn clone(&self) -> Self {
+        BitGrid {
+            width: self.width,
+            height: self.height,
+            bits: Bits::Owned(self.bytes().to_vec()),
+        }
+    }
+}
+
+impl std::fmt::Debug for BitGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BitGrid")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+impl Map2D<char> for BitGrid {
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn get(&self, coords: Coords2D) -> &char {
+        if self.bit(coords) {
+            &WALKABLE
+        } else {
+            &WALL
+        }
+    }
+
+    fn is_out_of_bound(&self, coords: Coords2D) -> bool {
+        coords.0 >= self.width || coords.1 >= self.height
+    }
+
+    fn is_traversable(&self, tile: Coords2D) -> bool {
+        !self.is_out_of_bound(tile) && self.bit(tile)
+    }
+
+    fn is_traversable_from(&self, from: Coords2D, to: Coords2D) -> bool {
+        let straight = (from.0 == to.0 && (from.1 as isize - to.1 as isize).abs() == 1)
+            || (from.1 == to.1 && (from.0 as isize - to.0 as isize).abs() == 1);
+        !self.is_out_of_bound(from) && straight && self.is_traversable(to)
+    }
+
+    /// Not supported, use 'tiles' instead
+    /// CoordsIter can only be created by a MovingAiMap, which needs a char for every tile
+    fn coords(&self) -> CoordsIter {
+        panic!("BitGrid does not support Map2D::coords, iterate over grid::tiles instead")
+    }
+
+    fn free_states(&self) -> usize {
+        let full_bytes = (self.width * self.height) / 8;
+        let bytes = self.bytes();
+        let full: usize = bytes[..full_bytes]
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum();
+        let rest = (full_bytes * 8..self.width * self.height)
+            .filter(|&index| bytes[index / 8] & (1 << (index % 8)) != 0)
+            .count();
+        full + rest
+    }
+
+    fn neighbors(&self, (x, y): Coords2D) -> Vec<Coords2D> {
+        let candidates = [
+            (x.checked_add(1), Some(y)),
+            (Some(x), y.checked_add(1)),
+            (Some(x), y.checked_sub(1)),
+            (x.checked_sub(1), Some(y)),
+        ];
+        candidates
+            .iter()
+            .filter_map(|&(x, y)| Some((x?, y?)))
+            .filter(|&next| self.is_traversable_from((x, y), next))
+            .collect()
+    }
+}
@@ -0,0 +1,273 @@
+// Jump point search, a port of blitz-path's jps_path that keeps its memory in the SearchArena
+// Nodes are pushed to and popped from the open list in the same order as in blitz-path, so the paths are
+// identical, but the closed list is a stamped array instead of a list that is scanned for every node
+// Positions next to the map are indexed through a border of one tile, so starts in the first row or
+// column no longer underflow
+
+use std::cmp::Ordering;
+
+use movingai::Coords2D;
+use movingai::Map2D;
+
+use crate::arena::{with_arena, SearchArena};
+use crate::search::SearchResult;
+
+#[derive(Copy, Clone, Debug)]
+pub struct JumpNode {
+    f: f64,
+    g: f64,
+    position: Coords2D,
+    parent: Coords2D,
+}
+
+impl JumpNode {
+    fn from_parent(parent: &JumpNode, position: Coords2D, goal: Coords2D) -> Self {
+        let g = parent.g + distance(parent.position, position);
+        JumpNode {
+            f: g + distance(position, goal),
+            g,
+            position,
+            parent: parent.position,
+        }
+    }
+}
+
+impl PartialEq for JumpNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for JumpNode {}
+
+impl PartialOrd for JumpNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JumpNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Lowest f first without a tie break, like blitz-path
+        other.f.partial_cmp(&self.f).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Direction {
+    Vertical(isize),
+    Horizontal(isize),
+    Diagonal(isize, isize),
+}
+
+fn distance(a: Coords2D, b: Coords2D) -> f64 {
+    let (x, y) = (a.0 as f64, a.1 as f64);
+    let (p, q) = (b.0 as f64, b.1 as f64);
+    ((x - p) * (x - p) + (y - q) * (y - q)).sqrt()
+}
+
+fn shift(tile: Coords2D, (dx, dy): (isize, isize)) -> Coords2D {
+    (
+        tile.0.wrapping_add(dx as usize),
+        tile.1.wrapping_add(dy as usize),
+    )
+}
+
+/// Step direction from 'parent' towards 'current'
+fn direction(current: Coords2D, parent: Coords2D) -> (isize, isize) {
+    (
+        current.0.cmp(&parent.0) as isize,
+        current.1.cmp(&parent.1) as isize,
+    )
+}
+
+/// Row-major index of a tile on the map with a border of one tile on every side
+fn padded_index((x, y): Coords2D, padded_width: usize) -> usize {
+    y.wrapping_add(1) * padded_width + x.wrapping_add(1)
+}
+
+fn padded_position(index: usize, padded_width: usize) -> Coords2D {
+    (
+        (index % padded_width).wrapping_sub(1),
+        (index / padded_width).wrapping_sub(1),
+    )
+}
+
+/// Same search as blitz_path::jps_path: 8 directions, corners are cut, euclidean distances
+pub fn jps<U, T: Map2D<U>>(map: &T, start: Coords2D, goal: Coords2D) -> SearchResult {
+    with_arena(|arena| jps_in(arena, map, start, goal))
+}
+
+pub fn jps_in<U, T: Map2D<U>>(
+    arena: &mut SearchArena,
+    map: &T,
+    start: Coords2D,
+    goal: Coords2D,
+) -> SearchResult {
+    if start == goal {
+        return SearchResult::new(vec![start], 0.0, Some(0));
+    }
+    // Nodes are at most one tile outside of the map, which wraps around to usize::MAX on the left and top
+    let padded_width = map.width() + 2;
+    let index = |tile: Coords2D| padded_index(tile, padded_width);
+    arena.begin(padded_width * (map.height() + 2));
+
+    let start_node = JumpNode {
+        f: distance(start, goal),
+        g: 0.0,
+        position: start,
+        parent: start,
+    };
+    // All 8 neighbours and the start itself, JPS has no direction to expand the start in
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let node = JumpNode::from_parent(&start_node, shift(start, (dx, dy)), goal);
+            arena.jumps.push(node);
+            arena.counters.generate(arena.jumps.len());
+        }
+    }
+    arena.nodes.close(index(start));
+    arena.nodes.update(index(start), 0.0, index(start));
+
+    let mut nodes_expanded = 0;
+    while let Some(current) = arena.jumps.pop() {
+        if current.position == goal {
+            let waypoints = rewind(arena, &current, padded_width);
+            return SearchResult::new(waypoints, current.g, Some(nodes_expanded));
+        }
+        if arena.nodes.is_closed(index(current.position)) {
+            continue;
+        }
+        nodes_expanded += 1;
+        arena.counters.expand(current.position);
+
+        let mut jump_points = std::mem::take(&mut arena.jump_points);
+        let (dx, dy) = direction(current.position, current.parent);
+        let direction = match (dx, dy) {
+            (0, 0) => None,
+            (0, dy) => Some(Direction::Vertical(dy)),
+            (dx, 0) => Some(Direction::Horizontal(dx)),
+            (dx, dy) => Some(Direction::Diagonal(dx, dy)),
+        };
+        if let Some(direction) = direction {
+            expand(map, &current, direction, goal, &mut jump_points);
+        }
+        for node in jump_points.drain(..) {
+            arena.jumps.push(node);
+            arena.counters.generate(arena.jumps.len());
+        }
+        arena.jump_points = jump_points;
+
+        arena.nodes.close(index(current.position));
+        arena
+            .nodes
+            .update(index(current.position), current.g, index(current.parent));
+    }
+
+    SearchResult::not_found(Some(nodes_expanded))
+}
+
+/// Walks from the goal back to the start, filling in the tiles between the jump points
+fn rewind(arena: &SearchArena, goal: &JumpNode, padded_width: usize) -> Vec<Coords2D> {
+    let index = |tile: Coords2D| padded_index(tile, padded_width);
+    let mut steps = vec![goal.position];
+    let (mut node, mut parent) = (goal.position, goal.parent);
+    while parent != node {
+        // blitz-path looks through its closed list first and then through the remaining open list
+        let parent_of_parent = if arena.nodes.is_closed(index(parent)) {
+            padded_position(arena.nodes.parent(index(parent)), padded_width)
+        } else {
+            match arena.jumps.iter().find(|open| open.position == parent) {
+                Some(open) => open.parent,
+                None => break,
+            }
+        };
+        let step = direction(parent, node);
+        let mut next = shift(node, step);
+        while next != parent {
+            steps.push(next);
+            next = shift(next, step);
+        }
+        steps.push(parent);
+        node = parent;
+        parent = parent_of_parent;
+    }
+    steps.reverse();
+    steps
+}
+
+fn expand<U, T: Map2D<U>>(
+    map: &T,
+    from: &JumpNode,
+    direction: Direction,
+    goal: Coords2D,
+    nodes: &mut Vec<JumpNode>,
+) {
+    let found_before = nodes.len();
+    let mut current = *from;
+    loop {
+        if current.position == goal {
+            nodes.push(current);
+            return;
+        }
+        if !map.is_traversable(current.position) {
+            return;
+        }
+        let step = match direction {
+            Direction::Vertical(dy) => {
+                forced_vertical(map, &current, dy, goal, nodes);
+                (0, dy)
+            }
+            Direction::Horizontal(dx) => {
+                forced_horizontal(map, &current, dx, goal, nodes);
+                (dx, 0)
+            }
+            Direction::Diagonal(dx, dy) => {
+                expand(map, &current, Direction::Horizontal(dx), goal, nodes);
+                expand(map, &current, Direction::Vertical(dy), goal, nodes);
+                (dx, dy)
+            }
+        };
+        let next = shift(current.position, step);
+        // A jump point: its forced neighbours, itself and the next tile to continue in this direction
+        if nodes.len() > found_before {
+            let next_node = JumpNode::from_parent(&current, next, goal);
+            nodes.push(current);
+            nodes.push(next_node);
+            return;
+        }
+        current = JumpNode::from_parent(from, next, goal);
+    }
+}
+
+fn forced_horizontal<U, T: Map2D<U>>(
+    map: &T,
+    node: &JumpNode,
+    dx: isize,
+    goal: Coords2D,
+    nodes: &mut Vec<JumpNode>,
+) {
+    let (x, y) = node.position;
+    let next_x = x.wrapping_add(dx as usize);
+    for &side_y in [y.wrapping_sub(1), y.wrapping_add(1)].iter() {
+        if !map.is_traversable((x, side_y)) && map.is_traversable((next_x, side_y)) {
+            nodes.push(JumpNode::from_parent(node, (next_x, side_y), goal));
+        }
+    }
+}
+
+fn forced_vertical<U, T: Map2D<U>>(
+    map: &T,
+    node: &JumpNode,
+    dy: isize,
+    goal: Coords2D,
+    nodes: &mut Vec<JumpNode>,
+) {
+    let (x, y) = node.position;
+    let next_y = y.wrapping_add(dy as usize);
+    for &side_x in [x.wrapping_sub(1), x.wrapping_add(1)].iter() {
+        if !map.is_traversable((side_x, y)) && map.is_traversable((side_x, next_y)) {
+            nodes.push(JumpNode::from_parent(node, (side_x, next_y), goal));
+        }
+    }
+}
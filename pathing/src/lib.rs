@@ -0,0 +1,13 @@
+// Grids and searches of my_library that do not depend on python
+// Shared by the python extension and the command line tool, which can not link against pyo3
+
+pub mod arena;
+pub mod components;
+pub mod costs;
+pub mod formats;
+pub mod grid;
+pub mod jps;
+pub mod pathfinding;
+pub mod render;
+pub mod search;
+pub mod state;
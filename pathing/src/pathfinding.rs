@@ -0,0 +1,617 @@
+// Grid search with configurable movement rules
+// blitz-path only offers a fixed ruleset per algorithm, this module adds an A* where connectivity,
+// corner cutting and heuristic can be chosen per query
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use movingai::Coords2D;
+use movingai::Map2D;
+
+use crate::arena::{with_arena, NodeTable, SearchArena};
+use crate::costs::CostGrid;
+use crate::search::SearchResult;
+
+const SQRT_2: f64 = std::f64::consts::SQRT_2;
+
+/// Estimate of the remaining distance to the goal
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Heuristic {
+    Manhattan,
+    Octile,
+    Euclidean,
+    Chebyshev,
+}
+
+impl Heuristic {
+    pub fn distance(self, a: Coords2D, b: Coords2D) -> f64 {
+        let dx = (a.0 as f64 - b.0 as f64).abs();
+        let dy = (a.1 as f64 - b.1 as f64).abs();
+        match self {
+            Heuristic::Manhattan => dx + dy,
+            Heuristic::Octile => dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy),
+            Heuristic::Euclidean => (dx * dx + dy * dy).sqrt(),
+            Heuristic::Chebyshev => dx.max(dy),
+        }
+    }
+}
+
+impl FromStr for Heuristic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(Heuristic::Manhattan),
+            "octile" => Ok(Heuristic::Octile),
+            "euclidean" => Ok(Heuristic::Euclidean),
+            "chebyshev" => Ok(Heuristic::Chebyshev),
+            _ => Err(format!(
+                "Unknown heuristic '{}', expected one of: manhattan, octile, euclidean, chebyshev",
+                s
+            )),
+        }
+    }
+}
+
+/// When a diagonal step may pass the corner of a wall
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CornerCutting {
+    /// Both orthogonal neighbours of the diagonal step have to be walkable
+    Never,
+    /// At least one orthogonal neighbour has to be walkable
+    Single,
+    /// Diagonal steps are allowed even between two walls
+    Always,
+}
+
+impl FromStr for CornerCutting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(CornerCutting::Never),
+            "single" => Ok(CornerCutting::Single),
+            "always" => Ok(CornerCutting::Always),
+            _ => Err(format!(
+                "Unknown corner cutting policy '{}', expected one of: never, single, always",
+                s
+            )),
+        }
+    }
+}
+
+/// Which search is run underneath
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Algorithm {
+    /// JPS when the options allow it, A* otherwise
+    Auto,
+    AStar,
+    Jps,
+    /// A* from start and goal at the same time, quick to give up on goals that can not be reached
+    Bidirectional,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Algorithm::Auto),
+            "astar" => Ok(Algorithm::AStar),
+            "jps" => Ok(Algorithm::Jps),
+            "bidirectional" => Ok(Algorithm::Bidirectional),
+            _ => Err(format!(
+                "Unknown algorithm '{}', expected one of: auto, astar, jps, bidirectional",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchOptions {
+    /// 8 connectivity if true, 4 connectivity otherwise
+    pub diagonal: bool,
+    pub corner_cutting: CornerCutting,
+    pub heuristic: Heuristic,
+    /// Tiles with less clearance than this are treated as walls, applied by RustPixelMap through ClearanceMap
+    pub unit_radius: f64,
+}
+
+impl SearchOptions {
+    /// blitz-path's JPS always moves in 8 directions, cuts corners and uses the euclidean distance
+    pub fn supports_jps(&self) -> bool {
+        self.diagonal
+            && self.corner_cutting == CornerCutting::Always
+            && self.heuristic == Heuristic::Euclidean
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            diagonal: true,
+            corner_cutting: CornerCutting::Never,
+            heuristic: Heuristic::Octile,
+            unit_radius: 0.0,
+        }
+    }
+}
+
+const STRAIGHT_DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const DIAGONAL_DIRECTIONS: [(isize, isize); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+fn shift(tile: Coords2D, (dx, dy): (isize, isize)) -> Coords2D {
+    // Wraps around on negative coordinates, which is then caught by the out of bound check of the map
+    (
+        tile.0.wrapping_add(dx as usize),
+        tile.1.wrapping_add(dy as usize),
+    )
+}
+
+/// Walkable neighbours of a tile and the cost to step there
+pub fn neighbors<U, T: Map2D<U>>(
+    map: &T,
+    tile: Coords2D,
+    options: &SearchOptions,
+) -> Vec<(Coords2D, f64)> {
+    let mut result = Vec::with_capacity(8);
+    neighbors_into(map, tile, options, &mut result);
+    result
+}
+
+/// Same as 'neighbors', appended to a buffer that can be reused for every tile
+pub fn neighbors_into<U, T: Map2D<U>>(
+    map: &T,
+    tile: Coords2D,
+    options: &SearchOptions,
+    result: &mut Vec<(Coords2D, f64)>,
+) {
+    for &direction in STRAIGHT_DIRECTIONS.iter() {
+        let next = shift(tile, direction);
+        if map.is_traversable(next) {
+            result.push((next, 1.0));
+        }
+    }
+    if !options.diagonal {
+        return;
+    }
+    for &(dx, dy) in DIAGONAL_DIRECTIONS.iter() {
+        let next = shift(tile, (dx, dy));
+        if !map.is_traversable(next) {
+            continue;
+        }
+        let free_corners = map.is_traversable(shift(tile, (dx, 0))) as u8
+            + map.is_traversable(shift(tile, (0, dy))) as u8;
+        let allowed = match options.corner_cutting {
+            CornerCutting::Never => free_corners == 2,
+            CornerCutting::Single => free_corners >= 1,
+            CornerCutting::Always => true,
+        };
+        if allowed {
+            result.push((next, SQRT_2));
+        }
+    }
+}
+
+/// Open list entry, 'index' is the row-major index of the tile
+#[derive(Copy, Clone, Debug)]
+pub struct OpenNode {
+    pub f: f64,
+    pub g: f64,
+    /// Estimate of the remaining cost, kept as is because f - g is not exact after rounding
+    pub h: f64,
+    pub index: usize,
+}
+
+impl OpenNode {
+    pub fn new(g: f64, h: f64, index: usize) -> Self {
+        OpenNode {
+            f: g + h,
+            g,
+            h,
+            index,
+        }
+    }
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max heap: lowest f first, ties are broken towards the higher g
+        other
+            .f
+            .partial_cmp(&self.f)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.g.partial_cmp(&other.g).unwrap_or(Ordering::Equal))
+    }
+}
+
+/// Base distance of the step multiplied by the weight of the entered tile, if a cost grid is given
+pub fn weighted(costs: Option<&CostGrid>, step_cost: f64, index: usize) -> f64 {
+    match costs {
+        Some(costs) => step_cost * costs.weight(index),
+        None => step_cost,
+    }
+}
+
+/// Bounds of a search that give up on the goal early
+/// A limited search that does not reach the goal returns a partial path to the closest explored tile instead
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    /// Number of nodes that may be taken from the open list
+    pub max_expansions: Option<usize>,
+    /// Tiles whose travel cost from the start is higher than this are not explored
+    pub max_distance: Option<f64>,
+}
+
+impl SearchLimits {
+    pub fn is_limited(&self) -> bool {
+        self.max_expansions.is_some() || self.max_distance.is_some()
+    }
+}
+
+/// Tiles from the start of the search to 'index', following the parents of the table
+fn rewind(nodes: &NodeTable, index: usize, width: usize) -> Vec<Coords2D> {
+    let mut steps = vec![(index % width, index / width)];
+    let mut node = index;
+    while nodes.parent(node) != usize::MAX {
+        node = nodes.parent(node);
+        steps.push((node % width, node / width));
+    }
+    steps.reverse();
+    steps
+}
+
+/// A* search with the given movement rules, using the search arena of the current thread
+pub fn astar<U, T: Map2D<U>>(
+    map: &T,
+    costs: Option<&CostGrid>,
+    start: Coords2D,
+    goal: Coords2D,
+    options: &SearchOptions,
+    limits: &SearchLimits,
+) -> SearchResult {
+    with_arena(|arena| astar_in(arena, map, costs, start, goal, options, limits))
+}
+
+pub fn astar_in<U, T: Map2D<U>>(
+    arena: &mut SearchArena,
+    map: &T,
+    costs: Option<&CostGrid>,
+    start: Coords2D,
+    goal: Coords2D,
+    options: &SearchOptions,
+    limits: &SearchLimits,
+) -> SearchResult {
+    let path = astar_by::<_, _, Infallible, _, _>(
+        arena,
+        map,
+        costs,
+        start,
+        goal,
+        options,
+        limits,
+        |_, _, cost| Ok(cost),
+        |tile| Ok(options.heuristic.distance(tile, goal)),
+    );
+    match path {
+        Ok(path) => path,
+        Err(never) => match never {},
+    }
+}
+
+/// A* where the caller decides the cost of every step and the estimate of the remaining cost
+/// 'step_cost' gets both tiles and the cost of the step by the cost grid, an infinite cost forbids the step
+/// 'estimate' gets a tile and has to stay below the real remaining cost for the path to be the shortest one
+/// The first error of either function stops the search and is returned
+#[allow(clippy::too_many_arguments)]
+pub fn astar_by<U, T, E, C, H>(
+    arena: &mut SearchArena,
+    map: &T,
+    costs: Option<&CostGrid>,
+    start: Coords2D,
+    goal: Coords2D,
+    options: &SearchOptions,
+    limits: &SearchLimits,
+    mut step_cost: C,
+    mut estimate: H,
+) -> Result<SearchResult, E>
+where
+    T: Map2D<U>,
+    C: FnMut(Coords2D, Coords2D, f64) -> Result<f64, E>,
+    H: FnMut(Coords2D) -> Result<f64, E>,
+{
+    if start == goal {
+        return Ok(SearchResult::new(vec![start], 0.0, Some(0)));
+    }
+    // A limited search still walks towards a blocked goal
+    if !map.is_traversable(start) || (!map.is_traversable(goal) && !limits.is_limited()) {
+        return Ok(SearchResult::not_found(Some(0)));
+    }
+
+    let width = map.width();
+    let index = |tile: Coords2D| tile.1 * width + tile.0;
+    arena.begin(width * map.height());
+    let mut successors = std::mem::take(&mut arena.successors);
+    let mut nodes_expanded = 0;
+    let max_distance = limits.max_distance.unwrap_or(f64::INFINITY);
+    let start_estimate = estimate(start)?;
+    // Explored tile closest to the goal, ties go to the cheaper one, only needed for partial paths
+    let mut closest = (start_estimate, 0.0, index(start));
+
+    arena.nodes.update(index(start), 0.0, usize::MAX);
+    arena
+        .open
+        .push(OpenNode::new(0.0, start_estimate, index(start)));
+    arena.counters.generate(arena.open.len());
+
+    let mut found = None;
+    let mut error = None;
+    'search: while let Some(current) = arena.open.pop() {
+        if arena.nodes.is_closed(current.index) {
+            continue;
+        }
+        if limits
+            .max_expansions
+            .is_some_and(|max| nodes_expanded >= max)
+        {
+            break;
+        }
+        arena.nodes.close(current.index);
+        nodes_expanded += 1;
+        let tile = (current.index % width, current.index / width);
+        arena.counters.expand(tile);
+
+        if tile == goal {
+            found = Some((rewind(&arena.nodes, current.index, width), current.g));
+            break;
+        }
+        if limits.is_limited() && (current.h, current.g) < (closest.0, closest.1) {
+            closest = (current.h, current.g, current.index);
+        }
+
+        successors.clear();
+        neighbors_into(map, tile, options, &mut successors);
+        for &(successor, base_cost) in successors.iter() {
+            let successor_index = index(successor);
+            if arena.nodes.is_closed(successor_index) {
+                continue;
+            }
+            let step = step_cost(tile, successor, weighted(costs, base_cost, successor_index));
+            let g = match step {
+                Ok(step) => current.g + step,
+                Err(step_error) => {
+                    error = Some(step_error);
+                    break 'search;
+                }
+            };
+            if g < arena.nodes.g(successor_index) && g <= max_distance {
+                let h = match estimate(successor) {
+                    Ok(h) => h,
+                    Err(estimate_error) => {
+                        error = Some(estimate_error);
+                        break 'search;
+                    }
+                };
+                arena.nodes.update(successor_index, g, current.index);
+                arena.open.push(OpenNode::new(g, h, successor_index));
+                arena.counters.generate(arena.open.len());
+            }
+        }
+    }
+
+    arena.successors = successors;
+    if let Some(error) = error {
+        return Err(error);
+    }
+    Ok(match found {
+        Some((steps, cost)) => SearchResult::new(steps, cost, Some(nodes_expanded)),
+        None if limits.is_limited() => {
+            let (_, cost, index) = closest;
+            SearchResult::partial(
+                rewind(&arena.nodes, index, width),
+                cost,
+                Some(nodes_expanded),
+            )
+        }
+        None => SearchResult::not_found(Some(nodes_expanded)),
+    })
+}
+
+/// A* from both ends at once, the searches stop when neither can find a cheaper way than the best meeting point
+/// Fails as soon as one side runs out of nodes, so a goal in a small enclosed area is rejected without
+/// exploring the whole area around the start
+pub fn bidirectional_astar<U, T: Map2D<U>>(
+    map: &T,
+    costs: Option<&CostGrid>,
+    start: Coords2D,
+    goal: Coords2D,
+    options: &SearchOptions,
+) -> SearchResult {
+    with_arena(|arena| bidirectional_astar_in(arena, map, costs, start, goal, options))
+}
+
+pub fn bidirectional_astar_in<U, T: Map2D<U>>(
+    arena: &mut SearchArena,
+    map: &T,
+    costs: Option<&CostGrid>,
+    start: Coords2D,
+    goal: Coords2D,
+    options: &SearchOptions,
+) -> SearchResult {
+    if start == goal {
+        return SearchResult::new(vec![start], 0.0, Some(0));
+    }
+    if !map.is_traversable(start) || !map.is_traversable(goal) {
+        return SearchResult::not_found(Some(0));
+    }
+
+    let width = map.width();
+    let index = |tile: Coords2D| tile.1 * width + tile.0;
+    arena.begin(width * map.height());
+    arena.reverse.begin(width * map.height());
+    let mut successors = std::mem::take(&mut arena.successors);
+    let mut nodes_expanded = 0;
+    // Cost of the cheapest way found so far and the tile where both searches met on it
+    let mut best = (f64::INFINITY, usize::MAX);
+
+    arena.nodes.update(index(start), 0.0, usize::MAX);
+    arena.open.push(OpenNode::new(
+        0.0,
+        options.heuristic.distance(start, goal),
+        index(start),
+    ));
+    arena.counters.generate(arena.open.len());
+    arena.reverse.update(index(goal), 0.0, usize::MAX);
+    arena.reverse_open.push(OpenNode::new(
+        0.0,
+        options.heuristic.distance(goal, start),
+        index(goal),
+    ));
+    arena
+        .counters
+        .generate(arena.open.len() + arena.reverse_open.len());
+
+    while let (Some(forward), Some(reverse)) = (arena.open.peek(), arena.reverse_open.peek()) {
+        // Both f values are lower bounds of every way that is not known yet
+        if forward.f.max(reverse.f) >= best.0 {
+            break;
+        }
+        // The side with the smaller open list is expanded
+        let forward = arena.open.len() <= arena.reverse_open.len();
+        let SearchArena {
+            nodes,
+            reverse,
+            open,
+            reverse_open,
+            counters,
+            ..
+        } = arena;
+        let (nodes, other, open, other_open, target) = if forward {
+            (nodes, &*reverse, open, &*reverse_open, goal)
+        } else {
+            (reverse, &*nodes, reverse_open, &*open, start)
+        };
+
+        let current = open.pop().unwrap();
+        if nodes.is_closed(current.index) {
+            continue;
+        }
+        nodes.close(current.index);
+        nodes_expanded += 1;
+        let tile = (current.index % width, current.index / width);
+        counters.expand(tile);
+
+        successors.clear();
+        neighbors_into(map, tile, options, &mut successors);
+        for &(successor, step_cost) in successors.iter() {
+            let successor_index = index(successor);
+            if nodes.is_closed(successor_index) {
+                continue;
+            }
+            // Steps are paid on the tile that is entered, which is the current one when walking backwards
+            let entered = if forward {
+                successor_index
+            } else {
+                current.index
+            };
+            let g = current.g + weighted(costs, step_cost, entered);
+            if g < nodes.g(successor_index) {
+                nodes.update(successor_index, g, current.index);
+                open.push(OpenNode::new(
+                    g,
+                    options.heuristic.distance(successor, target),
+                    successor_index,
+                ));
+                counters.generate(open.len() + other_open.len());
+                let total = g + other.g(successor_index);
+                if total < best.0 {
+                    best = (total, successor_index);
+                }
+            }
+        }
+    }
+
+    arena.successors = successors;
+    let (cost, meeting) = best;
+    if meeting == usize::MAX {
+        return SearchResult::not_found(Some(nodes_expanded));
+    }
+    let mut steps = rewind(&arena.nodes, meeting, width);
+    let mut node = meeting;
+    while arena.reverse.parent(node) != usize::MAX {
+        node = arena.reverse.parent(node);
+        steps.push((node % width, node / width));
+    }
+    SearchResult::new(steps, cost, Some(nodes_expanded))
+}
+
+/// Walking distance from the nearest source for every tile, stored row-major
+/// 'parents' holds the index of the next tile towards the nearest source, usize::MAX for sources and unreachable tiles
+pub struct DistanceField {
+    pub distances: Vec<f64>,
+    pub parents: Vec<usize>,
+}
+
+/// Dijkstra search from all sources at once until the whole reachable area is explored
+/// The heuristic of the options is ignored, sources that are not walkable are skipped
+/// With a cost grid the distances are the weighted travel costs of walking from the tile to its nearest source
+pub fn dijkstra<U, T: Map2D<U>>(
+    map: &T,
+    costs: Option<&CostGrid>,
+    sources: &[Coords2D],
+    options: &SearchOptions,
+) -> DistanceField {
+    let width = map.width();
+    let index = |tile: Coords2D| tile.1 * width + tile.0;
+    let size = width * map.height();
+    let mut distances = vec![f64::INFINITY; size];
+    let mut parents = vec![usize::MAX; size];
+    let mut closed = vec![false; size];
+    let mut open = BinaryHeap::new();
+
+    for &source in sources.iter().filter(|&&source| map.is_traversable(source)) {
+        distances[index(source)] = 0.0;
+        open.push(OpenNode::new(0.0, 0.0, index(source)));
+    }
+
+    while let Some(current) = open.pop() {
+        if closed[current.index] {
+            continue;
+        }
+        closed[current.index] = true;
+        let tile = (current.index % width, current.index / width);
+
+        for (successor, step_cost) in neighbors(map, tile, options) {
+            let successor_index = index(successor);
+            if closed[successor_index] {
+                continue;
+            }
+            // Units walk towards the sources, so the step pays for the current tile it enters
+            let g = current.g + weighted(costs, step_cost, current.index);
+            if g < distances[successor_index] {
+                distances[successor_index] = g;
+                parents[successor_index] = current.index;
+                open.push(OpenNode::new(g, 0.0, successor_index));
+            }
+        }
+    }
+
+    DistanceField { distances, parents }
+}
@@ -0,0 +1,246 @@
+// Pictures of maps with paths, points, distance fields and region labels drawn on top, written as PNG
+// The PNG is stored without compression, which keeps the encoder small enough to not need a dependency
+
+use movingai::{Coords2D, Map2D};
+
+use crate::grid::tiles;
+
+pub type Color = [u8; 3];
+
+const WALL: Color = [40, 40, 40];
+const WALKABLE: Color = [235, 235, 235];
+
+/// Something to draw on top of the map, grids are row-major with one value per cell
+#[derive(Clone, Debug, PartialEq)]
+pub enum Overlay {
+    /// Cells connected by straight lines
+    Path(Vec<Coords2D>),
+    Points(Vec<Coords2D>),
+    /// Walkable cells from blue (lowest) to red (highest), infinite values are left out
+    Heatmap(Vec<f64>),
+    /// Every label >= 0 gets its own color, negative labels are left out
+    Labels(Vec<i32>),
+}
+
+/// Well separated colors for labels and overlays, golden ratio steps around the color wheel
+pub fn palette(index: usize) -> Color {
+    let hue = (index as f64 * 0.618_033_988_75).fract();
+    hsv_to_rgb(hue, 0.75, 0.9)
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Color {
+    let sector = hue * 6.0;
+    let fraction = sector.fract();
+    let (p, q, t) = (
+        value * (1.0 - saturation),
+        value * (1.0 - saturation * fraction),
+        value * (1.0 - saturation * (1.0 - fraction)),
+    );
+    let (r, g, b) = match sector as usize % 6 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+    [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]
+}
+
+/// Cells on the line between two cells, both included
+fn line(from: Coords2D, to: Coords2D) -> Vec<Coords2D> {
+    let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+    let (to_x, to_y) = (to.0 as isize, to.1 as isize);
+    let (dx, dy) = ((to_x - x).abs(), -(to_y - y).abs());
+    let (step_x, step_y) = ((to_x - x).signum(), (to_y - y).signum());
+    let mut error = dx + dy;
+    let mut cells = vec![(x as usize, y as usize)];
+    while (x, y) != (to_x, to_y) {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x as usize, y as usize));
+    }
+    cells
+}
+
+/// One color per map cell, row 0 is the top row of the picture
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    walkable: Vec<bool>,
+    pixels: Vec<Color>,
+}
+
+impl Canvas {
+    pub fn new<U, T: Map2D<U>>(map: &T) -> Self {
+        let walkable: Vec<bool> = tiles(map).map(|tile| map.is_traversable(tile)).collect();
+        let pixels = walkable
+            .iter()
+            .map(|&free| if free { WALKABLE } else { WALL })
+            .collect();
+        Canvas {
+            width: map.width(),
+            height: map.height(),
+            walkable,
+            pixels,
+        }
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    fn set(&mut self, (x, y): Coords2D, color: Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    /// Grids need one value per cell, 'color' is used for paths and points
+    pub fn draw(&mut self, overlay: &Overlay, color: Color) -> Result<(), String> {
+        let cells = self.width * self.height;
+        if let Some(len) = grid_len(overlay) {
+            if len != cells {
+                return Err(format!(
+                    "Expected {} values for a map of size {}x{}, got {}",
+                    cells, self.width, self.height, len
+                ));
+            }
+        }
+        match overlay {
+            Overlay::Path(waypoints) => {
+                for pair in waypoints.windows(2) {
+                    for cell in line(pair[0], pair[1]) {
+                        self.set(cell, color);
+                    }
+                }
+                if let [single] = waypoints.as_slice() {
+                    self.set(*single, color);
+                }
+            }
+            Overlay::Points(points) => {
+                for &point in points.iter() {
+                    self.set(point, color);
+                }
+            }
+            Overlay::Heatmap(values) => {
+                let shown: Vec<usize> = (0..cells)
+                    .filter(|&i| self.walkable[i] && values[i].is_finite())
+                    .collect();
+                let finite: Vec<f64> = shown.iter().map(|&i| values[i]).collect();
+                let low = finite.iter().cloned().fold(f64::INFINITY, f64::min);
+                let high = finite.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                for index in shown {
+                    let fraction = if high > low {
+                        (values[index] - low) / (high - low)
+                    } else {
+                        0.0
+                    };
+                    // Blue over green to red
+                    self.pixels[index] = hsv_to_rgb((1.0 - fraction) * 2.0 / 3.0, 0.8, 0.95);
+                }
+            }
+            Overlay::Labels(labels) => {
+                for (index, &label) in labels.iter().enumerate() {
+                    if label >= 0 {
+                        self.pixels[index] = palette(label as usize);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// PNG with every cell drawn as a 'scale' x 'scale' square
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        let scale = scale.max(1);
+        let (width, height) = (self.width * scale, self.height * scale);
+        // Every row starts with the filter type 0 (none)
+        let mut raw = Vec::with_capacity(height * (1 + 3 * width));
+        for y in 0..height {
+            raw.push(0);
+            let row = &self.pixels[(y / scale) * self.width..(y / scale + 1) * self.width];
+            for color in row.iter() {
+                for _ in 0..scale {
+                    raw.extend_from_slice(color);
+                }
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bit depth, truecolor, default compression, filter and no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Number of values of the overlays that cover the whole map
+fn grid_len(overlay: &Overlay) -> Option<usize> {
+    match overlay {
+        Overlay::Heatmap(values) => Some(values.len()),
+        Overlay::Labels(labels) => Some(labels.len()),
+        Overlay::Path(_) | Overlay::Points(_) => None,
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]);
+    let blocks: Vec<&[u8]> = if data.is_empty() {
+        vec![&[]]
+    } else {
+        data.chunks(MAX_BLOCK).collect()
+    };
+    for (index, block) in blocks.iter().enumerate() {
+        let last = index + 1 == blocks.len();
+        out.push(last as u8);
+        let length = block.len() as u16;
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
@@ -0,0 +1,47 @@
+// Result of a search, without python types so the searches can be used by the command line tool
+// path::RustPath wraps it for python
+
+use movingai::Coords2D;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// Ordered from start to goal, both included
+    pub waypoints: Vec<Coords2D>,
+    /// Total cost to travel along the waypoints, infinite if no path was found
+    pub cost: f64,
+    /// Number of nodes taken from the open list, None if the underlying search does not report it
+    pub nodes_expanded: Option<usize>,
+    /// False if the goal could not be reached and the waypoints only lead towards it
+    pub reached_goal: bool,
+}
+
+impl SearchResult {
+    pub fn new(waypoints: Vec<Coords2D>, cost: f64, nodes_expanded: Option<usize>) -> Self {
+        SearchResult {
+            waypoints,
+            cost,
+            nodes_expanded,
+            reached_goal: true,
+        }
+    }
+
+    /// No path exists between start and goal
+    pub fn not_found(nodes_expanded: Option<usize>) -> Self {
+        SearchResult {
+            waypoints: vec![],
+            cost: f64::INFINITY,
+            nodes_expanded,
+            reached_goal: false,
+        }
+    }
+
+    /// The goal was not reached, the waypoints end at the tile that got closest to it
+    pub fn partial(waypoints: Vec<Coords2D>, cost: f64, nodes_expanded: Option<usize>) -> Self {
+        SearchResult {
+            waypoints,
+            cost,
+            nodes_expanded,
+            reached_goal: false,
+        }
+    }
+}
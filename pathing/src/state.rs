@@ -0,0 +1,181 @@
+// Compact binary encoding of maps and points, used to pickle and copy the python classes and as map file format
+// Maps keep one bit per tile for walkability plus the optional weights, caches like the clearance
+// are rebuilt when a map is loaded again
+//
+// Map layout, all numbers little endian:
+//     "RPM" and the format version as ascii digit, currently '1'
+//     width and height as u64
+//     walkability packed as in grid::BitGrid, (width * height + 7) / 8 bytes
+//     1 byte that is 1 if weights follow, then width * height f64 weights
+
+use std::ops::Range;
+
+use movingai::Coords2D;
+
+const MAP_MAGIC: &[u8; 3] = b"RPM";
+const MAP_VERSION: u8 = b'1';
+const POINTS_MAGIC: &[u8; 4] = b"RPP1";
+
+/// Reads the encoded values in order, running out of bytes is an error instead of a panic
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], String> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("State ends before the {}", what))?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn magic(&mut self, magic: &[u8]) -> Result<(), String> {
+        if self.take(magic.len(), "header")? != magic {
+            return Err(format!(
+                "Unknown state, expected it to start with {:?}",
+                String::from_utf8_lossy(magic)
+            ));
+        }
+        Ok(())
+    }
+
+    fn u64(&mut self, what: &str) -> Result<usize, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8, what)?);
+        Ok(u64::from_le_bytes(bytes) as usize)
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.bytes.len() - self.offset {
+            0 => Ok(()),
+            extra => Err(format!("State has {} unexpected bytes at the end", extra)),
+        }
+    }
+}
+
+/// Everything of a map that can not be recomputed from the rest
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapState {
+    pub width: usize,
+    pub height: usize,
+    /// Packed walkability, see grid::BitGrid::bytes
+    pub walkable: Vec<u8>,
+    pub weights: Option<Vec<f64>>,
+}
+
+/// Where the parts of an encoded map are, lets the walkability of a file be used in place
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapLayout {
+    pub width: usize,
+    pub height: usize,
+    pub walkable: Range<usize>,
+    pub weights: Option<Vec<f64>>,
+}
+
+impl MapLayout {
+    pub fn read(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, offset: 0 };
+        reader.magic(MAP_MAGIC)?;
+        match reader.take(1, "version")?[0] {
+            MAP_VERSION => {}
+            version => {
+                return Err(format!(
+                    "Unsupported map format version '{}', expected '{}'",
+                    char::from(version),
+                    char::from(MAP_VERSION)
+                ))
+            }
+        }
+        let width = reader.u64("width")?;
+        let height = reader.u64("height")?;
+        let tiles = width
+            .checked_mul(height)
+            .ok_or_else(|| format!("Map size {}x{} is too large", width, height))?;
+        let start = reader.offset;
+        reader.take(tiles.div_ceil(8), "walkable tiles")?;
+        let walkable = start..reader.offset;
+        let weights = match reader.take(1, "weight flag")?[0] {
+            0 => None,
+            1 => Some(
+                reader
+                    .take(tiles.saturating_mul(8), "weights")?
+                    .chunks(8)
+                    .map(|chunk| {
+                        let mut bytes = [0; 8];
+                        bytes.copy_from_slice(chunk);
+                        f64::from_le_bytes(bytes)
+                    })
+                    .collect(),
+            ),
+            flag => return Err(format!("Invalid weight flag {}", flag)),
+        };
+        reader.finish()?;
+        Ok(MapLayout {
+            width,
+            height,
+            walkable,
+            weights,
+        })
+    }
+}
+
+impl MapState {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAP_MAGIC.to_vec();
+        bytes.push(MAP_VERSION);
+        bytes.extend_from_slice(&(self.width as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.walkable);
+        match &self.weights {
+            Some(weights) => {
+                bytes.push(1);
+                for weight in weights.iter() {
+                    bytes.extend_from_slice(&weight.to_le_bytes());
+                }
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let layout = MapLayout::read(bytes)?;
+        Ok(MapState {
+            width: layout.width,
+            height: layout.height,
+            walkable: bytes[layout.walkable].to_vec(),
+            weights: layout.weights,
+        })
+    }
+}
+
+pub fn encode_points(points: &[Coords2D]) -> Vec<u8> {
+    let mut bytes = POINTS_MAGIC.to_vec();
+    bytes.extend_from_slice(&(points.len() as u64).to_le_bytes());
+    for &(x, y) in points.iter() {
+        bytes.extend_from_slice(&(x as u64).to_le_bytes());
+        bytes.extend_from_slice(&(y as u64).to_le_bytes());
+    }
+    bytes
+}
+
+pub fn decode_points(bytes: &[u8]) -> Result<Vec<Coords2D>, String> {
+    let mut reader = Reader { bytes, offset: 0 };
+    reader.magic(POINTS_MAGIC)?;
+    let count = reader.u64("number of points")?;
+    // The count is checked against the length first, a broken count must not allocate
+    if count > bytes.len() / 16 {
+        return Err(format!("State ends before point {}", bytes.len() / 16));
+    }
+    let mut points = Vec::with_capacity(count);
+    for _ in 0..count {
+        points.push((reader.u64("x")?, reader.u64("y")?));
+    }
+    reader.finish()?;
+    Ok(points)
+}
@@ -0,0 +1,377 @@
+// Region decomposition of the walkable area, e.g. main base, natural and the ramps between them
+// The clearance map is flooded from its peaks downwards (watershed on the medial axis): every peak starts a region
+// and two regions merge where they meet unless the meeting point is much narrower than both of them,
+// those narrow meeting points are the chokes
+
+use std::collections::BTreeMap;
+
+use pyo3::prelude::*;
+use pyo3::PyObjectProtocol;
+
+use movingai::{Coords2D, Map2D};
+use ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2};
+
+use crate::components::WALL;
+use crate::error::PathError;
+use crate::RustPoint2;
+
+#[pyclass(name = "RustRegion")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustRegion {
+    #[pyo3(get)]
+    pub id: usize,
+    /// Number of cells
+    #[pyo3(get)]
+    pub area: usize,
+    /// Cell with the largest clearance, the middle of the open space
+    #[pyo3(get)]
+    pub center: Coords2D,
+    #[pyo3(get)]
+    pub clearance: f64,
+    /// Ids of the regions that share a choke with this region, ascending
+    #[pyo3(get)]
+    pub neighbors: Vec<usize>,
+}
+
+#[pyclass(name = "RustChoke")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustChoke {
+    /// Ids of the two regions, the smaller id first
+    #[pyo3(get)]
+    pub regions: (usize, usize),
+    /// Outermost cells of the border between the regions
+    #[pyo3(get)]
+    pub start: Coords2D,
+    #[pyo3(get)]
+    pub end: Coords2D,
+    /// Diameter of the widest unit that fits through
+    #[pyo3(get)]
+    pub width: f64,
+    /// Cells on both sides of the border
+    #[pyo3(get)]
+    pub points: Vec<Coords2D>,
+}
+
+/// Snapshot of the regions of a RustPixelMap, it does not follow later changes of the map
+#[pyclass(name = "RustMapAnalysis")]
+#[derive(Clone, Debug)]
+pub struct RustMapAnalysis {
+    width: usize,
+    height: usize,
+    /// Row-major region id of every cell, WALL for walls
+    labels: Vec<i32>,
+    regions: Vec<RustRegion>,
+    /// Sorted by their regions
+    chokes: Vec<RustChoke>,
+}
+
+/// Peaks of the clearance that were flooded so far, merged basins point at the basin with the higher peak
+struct Basins {
+    parent: Vec<usize>,
+    peak: Vec<f64>,
+    peak_cell: Vec<usize>,
+}
+
+impl Basins {
+    fn add(&mut self, cell: usize, clearance: f64) -> usize {
+        self.parent.push(self.parent.len());
+        self.peak.push(clearance);
+        self.peak_cell.push(cell);
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut basin: usize) -> usize {
+        while self.parent[basin] != basin {
+            self.parent[basin] = self.parent[self.parent[basin]];
+            basin = self.parent[basin];
+        }
+        basin
+    }
+}
+
+impl RustMapAnalysis {
+    /// Neighboring regions merge if the clearance where they meet is at least 'merge_ratio' times the peak
+    /// clearance of the smaller region, so 0 keeps every region connected and 1 splits at every dent
+    pub fn new<U, T: Map2D<U>>(map: &T, clearance: &[f64], merge_ratio: f64) -> Self {
+        let (width, height) = (map.width(), map.height());
+        let neighbors = |index: usize| {
+            let (x, y) = (index % width, index / width);
+            let mut result = Vec::with_capacity(4);
+            if x > 0 {
+                result.push(index - 1);
+            }
+            if y > 0 {
+                result.push(index - width);
+            }
+            if x + 1 < width {
+                result.push(index + 1);
+            }
+            if y + 1 < height {
+                result.push(index + width);
+            }
+            result
+        };
+
+        let mut order: Vec<usize> = (0..width * height)
+            .filter(|&index| map.is_traversable((index % width, index / width)))
+            .collect();
+        order.sort_by(|&a, &b| clearance[b].total_cmp(&clearance[a]).then(a.cmp(&b)));
+
+        let mut basins = Basins {
+            parent: vec![],
+            peak: vec![],
+            peak_cell: vec![],
+        };
+        let mut basin_of = vec![usize::MAX; width * height];
+        for &index in order.iter() {
+            let mut touching: Vec<usize> = neighbors(index)
+                .into_iter()
+                .filter(|&neighbor| basin_of[neighbor] != usize::MAX)
+                .map(|neighbor| basins.find(basin_of[neighbor]))
+                .collect();
+            touching.sort_by(|&a, &b| basins.peak[b].total_cmp(&basins.peak[a]).then(a.cmp(&b)));
+            touching.dedup();
+            let highest = match touching.first() {
+                Some(&highest) => highest,
+                None => {
+                    basin_of[index] = basins.add(index, clearance[index]);
+                    continue;
+                }
+            };
+            for &other in touching.iter().skip(1) {
+                let other = basins.find(other);
+                if other != highest && clearance[index] >= merge_ratio * basins.peak[other] {
+                    basins.parent[other] = highest;
+                }
+            }
+            basin_of[index] = highest;
+        }
+
+        // Region ids in row-major order of the first cell
+        let mut region_of_basin = vec![WALL; basins.parent.len()];
+        let mut labels = vec![WALL; width * height];
+        let mut regions: Vec<RustRegion> = vec![];
+        for index in 0..width * height {
+            if basin_of[index] == usize::MAX {
+                continue;
+            }
+            let root = basins.find(basin_of[index]);
+            if region_of_basin[root] == WALL {
+                region_of_basin[root] = regions.len() as i32;
+                let peak_cell = basins.peak_cell[root];
+                regions.push(RustRegion {
+                    id: regions.len(),
+                    area: 0,
+                    center: (peak_cell % width, peak_cell / width),
+                    clearance: basins.peak[root],
+                    neighbors: vec![],
+                });
+            }
+            labels[index] = region_of_basin[root];
+            regions[labels[index] as usize].area += 1;
+        }
+
+        // Cells on the border between two regions, (lower id side, higher id side)
+        let mut borders: BTreeMap<(usize, usize), (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+        for index in 0..width * height {
+            if labels[index] == WALL {
+                continue;
+            }
+            for neighbor in neighbors(index).into_iter().filter(|&n| n > index) {
+                let (a, b) = (labels[index], labels[neighbor]);
+                if b == WALL || a == b {
+                    continue;
+                }
+                let (low, high) = if a < b {
+                    (index, neighbor)
+                } else {
+                    (neighbor, index)
+                };
+                let key = (a.min(b) as usize, a.max(b) as usize);
+                let border = borders.entry(key).or_default();
+                border.0.push(low);
+                border.1.push(high);
+            }
+        }
+
+        let tile = |index: usize| (index % width, index / width);
+        let mut chokes = Vec::with_capacity(borders.len());
+        for ((a, b), (mut low_side, mut high_side)) in borders {
+            regions[a].neighbors.push(b);
+            regions[b].neighbors.push(a);
+            low_side.sort_unstable();
+            low_side.dedup();
+            high_side.sort_unstable();
+            high_side.dedup();
+            // The widest unit that crosses the border has to fit through the border cells of both sides
+            let widest = |side: &[usize]| {
+                side.iter()
+                    .map(|&index| 2.0 * clearance[index])
+                    .fold(0.0, f64::max)
+            };
+            let choke_width = widest(&low_side).min(widest(&high_side));
+            // The two cells of one side that are furthest apart
+            let distance = |p: usize, q: usize| {
+                let ((px, py), (qx, qy)) = (tile(p), tile(q));
+                (px as f64 - qx as f64).hypot(py as f64 - qy as f64)
+            };
+            let mut ends = (low_side[0], low_side[0]);
+            for (i, &p) in low_side.iter().enumerate() {
+                for &q in low_side[i + 1..].iter() {
+                    if distance(p, q) > distance(ends.0, ends.1) {
+                        ends = (p, q);
+                    }
+                }
+            }
+            let mut points: Vec<Coords2D> =
+                low_side.into_iter().chain(high_side).map(tile).collect();
+            points.sort_unstable_by_key(|&(x, y)| (y, x));
+            chokes.push(RustChoke {
+                regions: (a, b),
+                start: tile(ends.0),
+                end: tile(ends.1),
+                width: choke_width,
+                points,
+            });
+        }
+        for region in regions.iter_mut() {
+            region.neighbors.sort_unstable();
+        }
+
+        RustMapAnalysis {
+            width,
+            height,
+            labels,
+            regions,
+            chokes,
+        }
+    }
+
+    pub fn labels(&self) -> &[i32] {
+        &self.labels
+    }
+
+    pub fn regions(&self) -> &[RustRegion] {
+        &self.regions
+    }
+
+    pub fn chokes(&self) -> &[RustChoke] {
+        &self.chokes
+    }
+
+    /// Region containing the point, None for walls
+    pub fn region_at(&self, point: Coords2D) -> Result<Option<&RustRegion>, PathError> {
+        if point.0 >= self.width || point.1 >= self.height {
+            return Err(PathError::OutOfBounds {
+                point,
+                width: self.width,
+                height: self.height,
+            });
+        }
+        Ok(match self.labels[point.1 * self.width + point.0] {
+            WALL => None,
+            label => Some(&self.regions[label as usize]),
+        })
+    }
+
+    pub fn choke_between(&self, a: usize, b: usize) -> Option<&RustChoke> {
+        let key = (a.min(b), a.max(b));
+        self.chokes
+            .binary_search_by_key(&key, |choke| choke.regions)
+            .ok()
+            .map(|index| &self.chokes[index])
+    }
+}
+
+#[pymethods]
+impl RustMapAnalysis {
+    #[getter]
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    #[getter(regions)]
+    fn py_regions(&self) -> Vec<RustRegion> {
+        self.regions.clone()
+    }
+
+    #[getter(chokes)]
+    fn py_chokes(&self) -> Vec<RustChoke> {
+        self.chokes.clone()
+    }
+
+    #[name = "labels"]
+    fn py_labels<'py>(&self, py: Python<'py>) -> &'py PyArray2<i32> {
+        /// Region id of every cell as an array of shape (height, width), -1 for walls
+        Array2::from_shape_vec((self.height, self.width), self.labels.clone())
+            .unwrap()
+            .into_pyarray(py)
+    }
+
+    #[name = "region_at"]
+    fn py_region_at(&self, point: RustPoint2) -> PyResult<Option<RustRegion>> {
+        /// Region containing the point, None for walls, raises ValueError for points outside of the map
+        Ok(self.region_at(point.to_coords_2d())?.cloned())
+    }
+
+    #[name = "choke_between"]
+    fn py_choke_between(&self, a: usize, b: usize) -> Option<RustChoke> {
+        /// Choke between two region ids, None if the regions do not touch
+        self.choke_between(a, b).cloned()
+    }
+
+    fn chokes_of(&self, region: usize) -> Vec<RustChoke> {
+        /// All chokes leading out of a region
+        self.chokes
+            .iter()
+            .filter(|choke| choke.regions.0 == region || choke.regions.1 == region)
+            .cloned()
+            .collect()
+    }
+
+    fn choke_at(&self, point: RustPoint2) -> Option<RustChoke> {
+        /// Choke whose border contains the point
+        let point = point.to_coords_2d();
+        self.chokes
+            .iter()
+            .find(|choke| choke.points.contains(&point))
+            .cloned()
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustMapAnalysis {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustMapAnalysis(regions: {}, chokes: {})",
+            self.regions.len(),
+            self.chokes.len()
+        ))
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustRegion {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustRegion(id: {}, area: {}, center: {:?})",
+            self.id, self.area, self.center
+        ))
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustChoke {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustChoke(regions: {:?}, start: {:?}, end: {:?}, width: {})",
+            self.regions, self.start, self.end, self.width
+        ))
+    }
+}
@@ -0,0 +1,99 @@
+// Runs MovingAI scenarios to compare the found path lengths against the published optimal lengths
+
+use std::time::Instant;
+
+use pyo3::prelude::*;
+use pyo3::PyObjectProtocol;
+
+use movingai::{Coords2D, SceneRecord};
+
+use crate::error::PathError;
+use crate::path::RustPath;
+
+#[pyclass(name = "RustScenarioResult")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustScenarioResult {
+    #[pyo3(get)]
+    pub bucket: u32,
+    #[pyo3(get)]
+    pub start: Coords2D,
+    #[pyo3(get)]
+    pub goal: Coords2D,
+    /// Octile length without corner cutting, as given in the scenario file
+    #[pyo3(get)]
+    pub optimal_length: f64,
+    /// Cost of the path, None if no path was found
+    #[pyo3(get)]
+    pub jps_length: Option<f64>,
+    #[pyo3(get)]
+    pub jps_seconds: f64,
+    /// astar_path only moves in 4 directions, so this is not comparable to optimal_length, see octile_length
+    #[pyo3(get)]
+    pub astar_length: Option<f64>,
+    #[pyo3(get)]
+    pub astar_seconds: f64,
+    /// A* in 8 directions without corner cutting and the octile heuristic, the moves the optimal length is given for
+    #[pyo3(get)]
+    pub octile_length: Option<f64>,
+    #[pyo3(get)]
+    pub octile_seconds: f64,
+}
+
+/// Length and wall time of one search
+fn timed<F: Fn(Coords2D, Coords2D) -> Result<RustPath, PathError>>(
+    search: &F,
+    scenario: &SceneRecord,
+) -> (Option<f64>, f64) {
+    let started = Instant::now();
+    let path = search(scenario.start_pos, scenario.goal_pos);
+    let seconds = started.elapsed().as_secs_f64();
+    (path.ok().map(|path| path.cost), seconds)
+}
+
+pub fn run<J, A, O>(
+    scenarios: &[SceneRecord],
+    jps: J,
+    astar: A,
+    octile: O,
+) -> Vec<RustScenarioResult>
+where
+    J: Fn(Coords2D, Coords2D) -> Result<RustPath, PathError>,
+    A: Fn(Coords2D, Coords2D) -> Result<RustPath, PathError>,
+    O: Fn(Coords2D, Coords2D) -> Result<RustPath, PathError>,
+{
+    scenarios
+        .iter()
+        .map(|scenario| {
+            let (jps_length, jps_seconds) = timed(&jps, scenario);
+            let (astar_length, astar_seconds) = timed(&astar, scenario);
+            let (octile_length, octile_seconds) = timed(&octile, scenario);
+            RustScenarioResult {
+                bucket: scenario.bucket,
+                start: scenario.start_pos,
+                goal: scenario.goal_pos,
+                optimal_length: scenario.optimal_length,
+                jps_length,
+                jps_seconds,
+                astar_length,
+                astar_seconds,
+                octile_length,
+                octile_seconds,
+            }
+        })
+        .collect()
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustScenarioResult {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustScenarioResult(start: {:?}, goal: {:?}, optimal: {}, jps: {:?}, astar: {:?}, octile: {:?})",
+            self.start,
+            self.goal,
+            self.optimal_length,
+            self.jps_length,
+            self.astar_length,
+            self.octile_length
+        ))
+    }
+}
@@ -0,0 +1,214 @@
+// Distance to the closest wall for every tile
+// Big units only fit onto tiles whose clearance is at least their radius, the searches see the map
+// through ClearanceMap so they work unchanged for every unit size
+
+use std::sync::OnceLock;
+
+use movingai::{Coords2D, CoordsIter, Map2D};
+
+use crate::grid::tiles;
+
+/// Stands in for infinity, real infinity would turn into NaN in the transform below
+const FAR: f64 = 1e20;
+
+/// Squared distance to the closest zero of 'f' for every position, Felzenszwalb & Huttenlocher
+/// 'f' holds 0 for walls and FAR for free positions
+fn distance_transform_1d(f: &[f64]) -> Vec<f64> {
+    let n = f.len();
+    let mut distances = vec![0.0; n];
+    if n == 0 {
+        return distances;
+    }
+    // Parabolas of the lower envelope and the boundaries between them
+    let mut vertices = vec![0usize; n];
+    let mut boundaries = vec![0.0; n + 1];
+    let mut k = 0;
+    boundaries[0] = f64::NEG_INFINITY;
+    boundaries[1] = f64::INFINITY;
+    for q in 1..n {
+        let intersection = |vertex: usize| {
+            let (q_f, v_f) = (q as f64, vertex as f64);
+            ((f[q] + q_f * q_f) - (f[vertex] + v_f * v_f)) / (2.0 * q_f - 2.0 * v_f)
+        };
+        let mut s = intersection(vertices[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(vertices[k]);
+        }
+        k += 1;
+        vertices[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f64::INFINITY;
+    }
+    k = 0;
+    for (q, distance) in distances.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - vertices[k] as f64;
+        *distance = offset * offset + f[vertices[k]];
+    }
+    distances
+}
+
+/// Row-major free radius around every tile: euclidean distance from the tile center to the closest
+/// wall minus half a tile, 0 for walls
+/// Everything outside of the map counts as wall
+pub fn clearance<U, T: Map2D<U>>(map: &T) -> Vec<f64> {
+    // Padding of one wall tile on every side of the map
+    let (width, height) = (map.width() + 2, map.height() + 2);
+    let mut grid = vec![0.0; width * height];
+    for (x, y) in tiles(map) {
+        if map.is_traversable((x, y)) {
+            grid[(y + 1) * width + x + 1] = FAR;
+        }
+    }
+    for x in 0..width {
+        let column: Vec<f64> = (0..height).map(|y| grid[y * width + x]).collect();
+        for (y, distance) in distance_transform_1d(&column).into_iter().enumerate() {
+            grid[y * width + x] = distance;
+        }
+    }
+    for y in 0..height {
+        let row = distance_transform_1d(&grid[y * width..(y + 1) * width]);
+        grid[y * width..(y + 1) * width].copy_from_slice(&row);
+    }
+    tiles(map)
+        .map(|(x, y)| {
+            let distance = grid[(y + 1) * width + x + 1].sqrt();
+            (distance - 0.5).max(0.0)
+        })
+        .collect()
+}
+
+/// Clearance of a map that changes, the transform only runs once a unit radius or the values are needed
+#[derive(Clone, Debug, Default)]
+pub struct LazyClearance {
+    /// Clearance of every tile and the largest of them, which bounds how far a new wall reaches
+    values: OnceLock<(Vec<f64>, f64)>,
+}
+
+impl LazyClearance {
+    pub fn get<U, T: Map2D<U>>(&self, map: &T) -> &[f64] {
+        let (values, _) = self.values.get_or_init(|| {
+            let values = clearance(map);
+            let largest = values.iter().cloned().fold(0.0, f64::max);
+            (values, largest)
+        });
+        values
+    }
+
+    /// True once the transform ran
+    #[allow(dead_code)]
+    pub fn is_computed(&self) -> bool {
+        self.values.get().is_some()
+    }
+
+    /// The map as seen by a unit of the given radius, units of radius 0 do not need the clearance
+    pub fn view<'a, U, T: Map2D<U>>(&'a self, map: &'a T, unit_radius: f64) -> ClearanceMap<'a, T> {
+        let clearance = if unit_radius > 0.0 {
+            self.get(map)
+        } else {
+            &[]
+        };
+        ClearanceMap::new(map, clearance, unit_radius)
+    }
+
+    /// Lowers the clearance around a rectangle that just became walls
+    /// Only tiles closer to the new walls than to every old wall change, which are all within the largest
+    /// clearance of the map around the rectangle
+    pub fn block<U, T: Map2D<U>>(&mut self, map: &T, corner: Coords2D, far_corner: Coords2D) {
+        let (values, largest) = match self.values.get_mut() {
+            Some(values) => values,
+            None => return,
+        };
+        let (width, height) = (map.width(), map.height());
+        let reach = (*largest + 0.5).ceil() as usize;
+        // Distance along one axis to the range covered by the rectangle
+        let gap = |value: usize, low: usize, high: usize| {
+            (low.saturating_sub(value) + value.saturating_sub(high)) as f64
+        };
+        for y in corner.1.saturating_sub(reach)..=(far_corner.1 + reach).min(height - 1) {
+            for x in corner.0.saturating_sub(reach)..=(far_corner.0 + reach).min(width - 1) {
+                let (dx, dy) = (
+                    gap(x, corner.0, far_corner.0),
+                    gap(y, corner.1, far_corner.1),
+                );
+                let distance = (dx * dx + dy * dy).sqrt();
+                let value = &mut values[y * width + x];
+                *value = value.min((distance - 0.5).max(0.0));
+            }
+        }
+    }
+
+    /// Opened tiles can raise the clearance up to the next wall anywhere, so the transform runs again when needed
+    pub fn open(&mut self) {
+        self.values = OnceLock::new();
+    }
+}
+
+/// The map as seen by a unit of the given radius: tiles without enough clearance are walls
+pub struct ClearanceMap<'a, T> {
+    map: &'a T,
+    clearance: &'a [f64],
+    unit_radius: f64,
+}
+
+impl<'a, T> ClearanceMap<'a, T> {
+    /// 'clearance' may be empty for a unit radius of 0, every walkable tile fits such a unit
+    pub fn new(map: &'a T, clearance: &'a [f64], unit_radius: f64) -> Self {
+        ClearanceMap {
+            map,
+            clearance,
+            unit_radius,
+        }
+    }
+}
+
+impl<'a, U, T: Map2D<U>> Map2D<U> for ClearanceMap<'a, T> {
+    fn height(&self) -> usize {
+        self.map.height()
+    }
+
+    fn width(&self) -> usize {
+        self.map.width()
+    }
+
+    fn get(&self, coords: Coords2D) -> &U {
+        self.map.get(coords)
+    }
+
+    fn is_out_of_bound(&self, coords: Coords2D) -> bool {
+        self.map.is_out_of_bound(coords)
+    }
+
+    fn is_traversable(&self, tile: Coords2D) -> bool {
+        self.map.is_traversable(tile)
+            && (self.unit_radius <= 0.0
+                || self.clearance[tile.1 * self.map.width() + tile.0] >= self.unit_radius)
+    }
+
+    fn is_traversable_from(&self, from: Coords2D, to: Coords2D) -> bool {
+        self.is_traversable(from)
+            && self.is_traversable(to)
+            && self.map.is_traversable_from(from, to)
+    }
+
+    fn coords(&self) -> CoordsIter {
+        self.map.coords()
+    }
+
+    fn free_states(&self) -> usize {
+        tiles(self)
+            .filter(|&tile| self.is_traversable(tile))
+            .count()
+    }
+
+    fn neighbors(&self, tile: Coords2D) -> Vec<Coords2D> {
+        self.map
+            .neighbors(tile)
+            .into_iter()
+            .filter(|&next| self.is_traversable(next))
+            .collect()
+    }
+}
@@ -0,0 +1,58 @@
+// Errors of path queries and how they are raised in python
+
+use std::fmt;
+
+use movingai::Coords2D;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::PyErr;
+
+create_exception!(my_library, PathNotFoundError, PyException);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathError {
+    /// The point lies outside of the map
+    OutOfBounds {
+        point: Coords2D,
+        width: usize,
+        height: usize,
+    },
+    /// Start or goal is a wall
+    Blocked(Coords2D),
+    /// Start and goal are walkable but not connected
+    NotFound { start: Coords2D, goal: Coords2D },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::OutOfBounds {
+                point,
+                width,
+                height,
+            } => write!(
+                f,
+                "Point {:?} is outside of the map of size {}x{}",
+                point, width, height
+            ),
+            PathError::Blocked(point) => write!(f, "Point {:?} is not walkable", point),
+            PathError::NotFound { start, goal } => {
+                write!(f, "No path from {:?} to {:?}", start, goal)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// OutOfBounds is a ValueError, a blocked endpoint or a missing connection is a PathNotFoundError
+impl From<PathError> for PyErr {
+    fn from(error: PathError) -> PyErr {
+        match error {
+            PathError::OutOfBounds { .. } => PyValueError::new_err(error.to_string()),
+            PathError::Blocked(_) | PathError::NotFound { .. } => {
+                PathNotFoundError::new_err(error.to_string())
+            }
+        }
+    }
+}
@@ -0,0 +1,526 @@
+// Hierarchical path planning (HPA*) for long queries on big maps
+// The map is split into square clusters, the cells where a cluster border can be crossed become the nodes of a
+// small abstract graph and the walking distances between the nodes of each cluster are computed once
+// Queries search the abstract graph and only turn the chosen edges into cells afterwards, the paths are close to
+// but not always exactly the shortest ones
+
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use movingai::{Coords2D, Map2D};
+
+use crate::arena::{with_arena, SearchCounters};
+use crate::costs::CostGrid;
+use crate::pathfinding::{neighbors, weighted, OpenNode, SearchOptions};
+use crate::search::SearchResult;
+
+/// Entrances at least this wide get a transition at both ends instead of one in the middle
+const WIDE_ENTRANCE: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Bounds {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Bounds {
+    fn contains(&self, tile: Coords2D) -> bool {
+        tile.0 >= self.x
+            && tile.1 >= self.y
+            && tile.0 < self.x + self.width
+            && tile.1 < self.y + self.height
+    }
+
+    fn index(&self, tile: Coords2D) -> usize {
+        (tile.1 - self.y) * self.width + tile.0 - self.x
+    }
+
+    fn tile(&self, index: usize) -> Coords2D {
+        (self.x + index % self.width, self.y + index / self.width)
+    }
+
+    /// Cells along the edge of the bounds, each once
+    fn perimeter(&self) -> Vec<Coords2D> {
+        let (right, bottom) = (self.x + self.width - 1, self.y + self.height - 1);
+        let mut cells = Vec::with_capacity(2 * (self.width + self.height));
+        for y in self.y..=bottom {
+            for x in self.x..=right {
+                if y == self.y || y == bottom || x == self.x || x == right {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Cluster {
+    bounds: Bounds,
+    /// Transition cells inside of the cluster
+    nodes: Vec<Coords2D>,
+    /// Walking distance inside of the cluster from node i to node j at i * nodes.len() + j
+    distances: Vec<f64>,
+}
+
+/// Dijkstra that never leaves 'bounds', indices are local to the bounds
+/// Without 'forward' the distances are those from every cell to the source, e.g. to the goal of a query
+struct LocalField {
+    bounds: Bounds,
+    distances: Vec<f64>,
+    parents: Vec<usize>,
+}
+
+impl LocalField {
+    fn search<U, T: Map2D<U>>(
+        map: &T,
+        costs: Option<&CostGrid>,
+        options: &SearchOptions,
+        bounds: Bounds,
+        source: Coords2D,
+        targets: &[Coords2D],
+        forward: bool,
+    ) -> Self {
+        let size = bounds.width * bounds.height;
+        let mut field = LocalField {
+            bounds,
+            distances: vec![f64::INFINITY; size],
+            parents: vec![usize::MAX; size],
+        };
+        let mut closed = vec![false; size];
+        let mut remaining: HashSet<Coords2D> = targets.iter().cloned().collect();
+        let mut open = BinaryHeap::new();
+        field.distances[bounds.index(source)] = 0.0;
+        open.push(OpenNode::new(0.0, 0.0, bounds.index(source)));
+        while let Some(current) = open.pop() {
+            if closed[current.index] {
+                continue;
+            }
+            closed[current.index] = true;
+            let tile = bounds.tile(current.index);
+            remaining.remove(&tile);
+            if remaining.is_empty() {
+                break;
+            }
+            for (successor, step_cost) in neighbors(map, tile, options) {
+                if !bounds.contains(successor) {
+                    continue;
+                }
+                let successor_index = bounds.index(successor);
+                // Steps are paid on the tile that is entered, which is the current one when walking backwards
+                let entered = if forward { successor } else { tile };
+                let g = current.g + weighted(costs, step_cost, entered.1 * map.width() + entered.0);
+                if !closed[successor_index] && g < field.distances[successor_index] {
+                    field.distances[successor_index] = g;
+                    field.parents[successor_index] = current.index;
+                    open.push(OpenNode::new(g, 0.0, successor_index));
+                }
+            }
+        }
+        field
+    }
+
+    fn distance(&self, tile: Coords2D) -> f64 {
+        self.distances[self.bounds.index(tile)]
+    }
+
+    /// Cells from the source to 'tile', the source excluded
+    fn steps_to(&self, tile: Coords2D) -> Vec<Coords2D> {
+        let mut steps = vec![];
+        let mut index = self.bounds.index(tile);
+        while self.parents[index] != usize::MAX {
+            steps.push(self.bounds.tile(index));
+            index = self.parents[index];
+        }
+        steps.reverse();
+        steps
+    }
+}
+
+/// Border between a cluster and its neighbor to the right (East) or below (South)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Border {
+    East(usize, usize),
+    South(usize, usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Hierarchy {
+    cluster_size: usize,
+    width: usize,
+    height: usize,
+    options: SearchOptions,
+    clusters_x: usize,
+    clusters_y: usize,
+    /// Row-major like the cells
+    clusters: Vec<Cluster>,
+    /// Transition cells and the cells across the cluster border they can step to
+    transitions: HashMap<Coords2D, Vec<Coords2D>>,
+}
+
+impl Hierarchy {
+    pub fn new<U, T: Map2D<U>>(
+        map: &T,
+        costs: Option<&CostGrid>,
+        cluster_size: usize,
+        options: SearchOptions,
+    ) -> Self {
+        let (width, height) = (map.width(), map.height());
+        let clusters_x = width.div_ceil(cluster_size);
+        let clusters_y = height.div_ceil(cluster_size);
+        let mut clusters = Vec::with_capacity(clusters_x * clusters_y);
+        for cy in 0..clusters_y {
+            for cx in 0..clusters_x {
+                let (x, y) = (cx * cluster_size, cy * cluster_size);
+                clusters.push(Cluster {
+                    bounds: Bounds {
+                        x,
+                        y,
+                        width: cluster_size.min(width - x),
+                        height: cluster_size.min(height - y),
+                    },
+                    nodes: vec![],
+                    distances: vec![],
+                });
+            }
+        }
+        let mut hierarchy = Hierarchy {
+            cluster_size,
+            width,
+            height,
+            options,
+            clusters_x,
+            clusters_y,
+            clusters,
+            transitions: HashMap::new(),
+        };
+        for cy in 0..clusters_y {
+            for cx in 0..clusters_x {
+                if cx + 1 < clusters_x {
+                    hierarchy.add_transitions(map, Border::East(cx, cy));
+                }
+                if cy + 1 < clusters_y {
+                    hierarchy.add_transitions(map, Border::South(cx, cy));
+                }
+            }
+        }
+        for cluster in 0..hierarchy.clusters.len() {
+            hierarchy.rebuild_cluster(map, costs, cluster);
+        }
+        hierarchy
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.cluster_size
+    }
+
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    fn cluster_of(&self, tile: Coords2D) -> usize {
+        (tile.1 / self.cluster_size) * self.clusters_x + tile.0 / self.cluster_size
+    }
+
+    /// Pairs of cells facing each other across the border, first cell on the side of the upper left cluster
+    fn border_cells(&self, border: Border) -> Vec<(Coords2D, Coords2D)> {
+        match border {
+            Border::East(cx, cy) => {
+                let bounds = self.clusters[cy * self.clusters_x + cx].bounds;
+                let x = bounds.x + bounds.width - 1;
+                (bounds.y..bounds.y + bounds.height)
+                    .map(|y| ((x, y), (x + 1, y)))
+                    .collect()
+            }
+            Border::South(cx, cy) => {
+                let bounds = self.clusters[cy * self.clusters_x + cx].bounds;
+                let y = bounds.y + bounds.height - 1;
+                (bounds.x..bounds.x + bounds.width)
+                    .map(|x| ((x, y), (x, y + 1)))
+                    .collect()
+            }
+        }
+    }
+
+    fn link(&mut self, from: Coords2D, to: Coords2D) {
+        self.transitions.entry(from).or_default().push(to);
+    }
+
+    fn unlink(&mut self, from: Coords2D, to: Coords2D) {
+        if let Some(partners) = self.transitions.get_mut(&from) {
+            partners.retain(|&partner| partner != to);
+            if partners.is_empty() {
+                self.transitions.remove(&from);
+            }
+        }
+    }
+
+    /// Every run of cells that is walkable on both sides gets one transition, wide runs get one at each end
+    fn add_transitions<U, T: Map2D<U>>(&mut self, map: &T, border: Border) {
+        let cells = self.border_cells(border);
+        let open = |&(a, b): &(Coords2D, Coords2D)| map.is_traversable(a) && map.is_traversable(b);
+        let mut run_start = None;
+        for i in 0..=cells.len() {
+            match (run_start, i < cells.len() && open(&cells[i])) {
+                (None, true) => run_start = Some(i),
+                (Some(first), false) => {
+                    let last = i - 1;
+                    let picked = if last - first + 1 >= WIDE_ENTRANCE {
+                        vec![first, last]
+                    } else {
+                        vec![(first + last) / 2]
+                    };
+                    for pick in picked {
+                        let (a, b) = cells[pick];
+                        self.link(a, b);
+                        self.link(b, a);
+                    }
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn remove_transitions(&mut self, border: Border) {
+        for (a, b) in self.border_cells(border) {
+            self.unlink(a, b);
+            self.unlink(b, a);
+        }
+    }
+
+    fn rebuild_cluster<U, T: Map2D<U>>(
+        &mut self,
+        map: &T,
+        costs: Option<&CostGrid>,
+        cluster: usize,
+    ) {
+        let bounds = self.clusters[cluster].bounds;
+        let nodes: Vec<Coords2D> = bounds
+            .perimeter()
+            .into_iter()
+            .filter(|tile| self.transitions.contains_key(tile))
+            .collect();
+        let mut distances = Vec::with_capacity(nodes.len() * nodes.len());
+        for &node in nodes.iter() {
+            let field = LocalField::search(map, costs, &self.options, bounds, node, &nodes, true);
+            distances.extend(nodes.iter().map(|&other| field.distance(other)));
+        }
+        let cluster = &mut self.clusters[cluster];
+        cluster.nodes = nodes;
+        cluster.distances = distances;
+    }
+
+    /// Rebuilds the clusters touching the rectangle from 'corner' to 'far_corner' (inclusive) and their neighbors
+    /// Needs to be called after cells or weights in the rectangle changed
+    pub fn update<U, T: Map2D<U>>(
+        &mut self,
+        map: &T,
+        costs: Option<&CostGrid>,
+        corner: Coords2D,
+        far_corner: Coords2D,
+    ) {
+        let (cx0, cy0) = (corner.0 / self.cluster_size, corner.1 / self.cluster_size);
+        let cx1 = (far_corner.0.min(self.width - 1)) / self.cluster_size;
+        let cy1 = (far_corner.1.min(self.height - 1)) / self.cluster_size;
+        let mut borders = HashSet::new();
+        let mut affected = HashSet::new();
+        for cy in cy0..=cy1 {
+            for cx in cx0..=cx1 {
+                affected.insert(cy * self.clusters_x + cx);
+                if cx > 0 {
+                    borders.insert(Border::East(cx - 1, cy));
+                    affected.insert(cy * self.clusters_x + cx - 1);
+                }
+                if cx + 1 < self.clusters_x {
+                    borders.insert(Border::East(cx, cy));
+                    affected.insert(cy * self.clusters_x + cx + 1);
+                }
+                if cy > 0 {
+                    borders.insert(Border::South(cx, cy - 1));
+                    affected.insert((cy - 1) * self.clusters_x + cx);
+                }
+                if cy + 1 < self.clusters_y {
+                    borders.insert(Border::South(cx, cy));
+                    affected.insert((cy + 1) * self.clusters_x + cx);
+                }
+            }
+        }
+        for &border in borders.iter() {
+            self.remove_transitions(border);
+        }
+        for &border in borders.iter() {
+            self.add_transitions(map, border);
+        }
+        for cluster in affected {
+            self.rebuild_cluster(map, costs, cluster);
+        }
+    }
+
+    /// Searches the abstract graph, start and goal have to be walkable cells of the map
+    /// With 'refine' the waypoints are all cells of the path, otherwise only start, goal and the border
+    /// crossings in between, the parts between two waypoints then stay inside of one cluster
+    /// 'nodes_expanded' counts the nodes of the abstract graph
+    pub fn find_path<U, T: Map2D<U>>(
+        &self,
+        map: &T,
+        costs: Option<&CostGrid>,
+        start: Coords2D,
+        goal: Coords2D,
+        refine: bool,
+    ) -> SearchResult {
+        if start == goal {
+            return SearchResult::new(vec![start], 0.0, Some(0));
+        }
+        let start_cluster = &self.clusters[self.cluster_of(start)];
+        let goal_cluster = &self.clusters[self.cluster_of(goal)];
+
+        // Start and goal join the abstract graph for this query only
+        let mut targets = start_cluster.nodes.clone();
+        if start_cluster.bounds == goal_cluster.bounds {
+            targets.push(goal);
+        }
+        let field = LocalField::search(
+            map,
+            costs,
+            &self.options,
+            start_cluster.bounds,
+            start,
+            &targets,
+            true,
+        );
+        let start_edges: Vec<(Coords2D, f64)> = targets
+            .iter()
+            .map(|&target| (target, field.distance(target)))
+            .filter(|&(_, distance)| distance.is_finite())
+            .collect();
+        // One search backwards from the goal gives the distances of all nodes of its cluster to the goal
+        let field = LocalField::search(
+            map,
+            costs,
+            &self.options,
+            goal_cluster.bounds,
+            goal,
+            &goal_cluster.nodes,
+            false,
+        );
+        let goal_edges: HashMap<Coords2D, f64> = goal_cluster
+            .nodes
+            .iter()
+            .map(|&node| (node, field.distance(node)))
+            .filter(|&(_, distance)| distance.is_finite())
+            .collect();
+
+        let successors = |tile: Coords2D| {
+            let mut result = vec![];
+            if tile == start {
+                result.extend(start_edges.iter().cloned());
+            }
+            if let Some(partners) = self.transitions.get(&tile) {
+                result.extend(partners.iter().map(|&partner| {
+                    (
+                        partner,
+                        weighted(costs, 1.0, partner.1 * self.width + partner.0),
+                    )
+                }));
+                let cluster = &self.clusters[self.cluster_of(tile)];
+                if let Some(i) = cluster.nodes.iter().position(|&node| node == tile) {
+                    let row = &cluster.distances[i * cluster.nodes.len()..];
+                    result.extend(
+                        cluster
+                            .nodes
+                            .iter()
+                            .zip(row.iter())
+                            .filter(|&(&node, distance)| node != tile && distance.is_finite())
+                            .map(|(&node, &distance)| (node, distance)),
+                    );
+                }
+            }
+            if let Some(&distance) = goal_edges.get(&tile) {
+                result.push((goal, distance));
+            }
+            result
+        };
+
+        let index = |tile: Coords2D| tile.1 * self.width + tile.0;
+        let tile_of = |index: usize| (index % self.width, index / self.width);
+        let mut g_score: HashMap<usize, f64> = HashMap::new();
+        let mut parent: HashMap<usize, usize> = HashMap::new();
+        let mut closed = HashSet::new();
+        let mut open = BinaryHeap::new();
+        let mut nodes_expanded = 0;
+        // The search statistics count the abstract graph as well
+        let mut counters = SearchCounters::new(with_arena(|arena| arena.counters.tracing));
+        g_score.insert(index(start), 0.0);
+        open.push(OpenNode::new(
+            0.0,
+            self.options.heuristic.distance(start, goal),
+            index(start),
+        ));
+        counters.generate(open.len());
+        while let Some(current) = open.pop() {
+            if !closed.insert(current.index) {
+                continue;
+            }
+            nodes_expanded += 1;
+            let tile = tile_of(current.index);
+            counters.expand(tile);
+            if tile == goal {
+                let mut waypoints = vec![goal];
+                let mut node = current.index;
+                while let Some(&previous) = parent.get(&node) {
+                    waypoints.push(tile_of(previous));
+                    node = previous;
+                }
+                waypoints.reverse();
+                if refine {
+                    waypoints = self.refine(map, costs, &waypoints);
+                }
+                with_arena(|arena| arena.counters.merge(counters));
+                return SearchResult::new(waypoints, current.g, Some(nodes_expanded));
+            }
+            for (successor, cost) in successors(tile) {
+                let successor_index = index(successor);
+                let g = current.g + cost;
+                if !closed.contains(&successor_index)
+                    && g < *g_score.get(&successor_index).unwrap_or(&f64::INFINITY)
+                {
+                    g_score.insert(successor_index, g);
+                    parent.insert(successor_index, current.index);
+                    open.push(OpenNode::new(
+                        g,
+                        self.options.heuristic.distance(successor, goal),
+                        successor_index,
+                    ));
+                    counters.generate(open.len());
+                }
+            }
+        }
+        with_arena(|arena| arena.counters.merge(counters));
+        SearchResult::not_found(Some(nodes_expanded))
+    }
+
+    /// Fills in the cells between consecutive abstract waypoints
+    fn refine<U, T: Map2D<U>>(
+        &self,
+        map: &T,
+        costs: Option<&CostGrid>,
+        waypoints: &[Coords2D],
+    ) -> Vec<Coords2D> {
+        let mut cells = vec![waypoints[0]];
+        for pair in waypoints.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let cluster = self.cluster_of(from);
+            if cluster != self.cluster_of(to) {
+                // Straight step across a border
+                cells.push(to);
+                continue;
+            }
+            let bounds = self.clusters[cluster].bounds;
+            let field = LocalField::search(map, costs, &self.options, bounds, from, &[to], true);
+            cells.extend(field.steps_to(to));
+        }
+        cells
+    }
+}
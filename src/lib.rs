@@ -6,7 +6,6 @@ https://pyo3.rs/
 https://docs.rs/pyo3
 */
 #![allow(unused_doc_comments)]
-#![feature(cell_update)]
 // Testing and benchmark crate
 #![feature(test)]
 extern crate test;
@@ -18,19 +17,52 @@ use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use pyo3::PyObjectProtocol;
 
-use ndarray::{Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMut2, ArrayViewMutD};
-use numpy::{IntoPyArray, PyArray2, PyArrayDyn, PyReadonlyArray2, PyReadonlyArrayDyn};
-use pyo3::types::{PyDict, PyList, PySet};
+use ndarray::{
+    Array1, Array2, Array3, ArrayD, ArrayView2, ArrayViewD, ArrayViewMut2, ArrayViewMutD,
+};
+use numpy::{
+    IntoPyArray, PyArray1, PyArray2, PyArray3, PyArrayDyn, PyReadonlyArray2, PyReadonlyArrayDyn,
+};
+use pyo3::types::{PyBytes, PyDict, PyList, PySet, PyType};
 
-use blitz_path::a_star_path;
-use blitz_path::jps_path;
 use movingai::Coords2D;
-use movingai::MovingAiMap;
+use movingai::Map2D;
 use num_bigint::BigInt;
+use rayon::prelude::*;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+mod analysis;
+mod benchmark;
+mod clearance;
+mod error;
+mod hierarchical;
+mod line_of_sight;
+mod path;
+mod replanning;
+mod tasks;
+use analysis::{RustChoke, RustMapAnalysis, RustRegion};
+use arena::{with_arena, SearchCounters};
+use benchmark::RustScenarioResult;
+use clearance::{ClearanceMap, LazyClearance};
+use components::Components;
+use costs::{CostGrid, Falloff};
+use error::{PathError, PathNotFoundError};
+use grid::{BitGrid, MappedFile};
+use hierarchical::Hierarchy;
+use path::{RustPath, RustSearchStats, StatsMode};
+use pathfinding::{Algorithm, CornerCutting, Heuristic, SearchLimits, SearchOptions};
+pub use pathing::{
+    arena, components, costs, formats, grid, jps, pathfinding, render, search, state,
+};
+use render::{Canvas, Overlay};
+use replanning::{ChangeLog, DStarLite, RustPlanner};
+use search::SearchResult;
+use state::{MapLayout, MapState};
 
 /// Class example
-#[pyclass(name = "RustPoint2")]
+#[pyclass(name = "RustPoint2", module = "my_library")]
 #[derive(Copy, Clone, Debug)]
 pub struct RustPoint2 {
     #[pyo3(get, set)]
@@ -46,6 +78,7 @@ impl RustPoint2 {
         RustPoint2 { x: x_, y: y_ }
     }
 
+    #[allow(clippy::wrong_self_convention)]
     fn to_coords_2d(&self) -> Coords2D {
         (self.x, self.y)
     }
@@ -57,6 +90,37 @@ impl RustPoint2 {
     fn distance_to_squared(&self, other: &RustPoint2) -> f64 {
         ((self.x - other.x) as f64).powi(2) + ((self.y - other.y) as f64).powi(2)
     }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &state::encode_points(&[(self.x, self.y)]))
+    }
+
+    fn __setstate__(&mut self, state: &PyBytes) -> PyResult<()> {
+        match state::decode_points(state.as_bytes()) {
+            Ok(points) if points.len() == 1 => {
+                let (x, y) = points[0];
+                *self = RustPoint2 { x, y };
+                Ok(())
+            }
+            Ok(points) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Expected the state of 1 point, got {}",
+                points.len()
+            ))),
+            Err(error) => Err(pyo3::exceptions::PyValueError::new_err(error)),
+        }
+    }
+
+    fn __reduce__<'py>(&self, py: Python<'py>) -> (&'py PyType, (usize, usize)) {
+        (py.get_type::<RustPoint2>(), (self.x, self.y))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &PyDict) -> Self {
+        *self
+    }
 }
 
 #[pyproto]
@@ -69,40 +133,1490 @@ impl PyObjectProtocol for RustPoint2 {
     }
 }
 
-#[pyclass(name = "RustPixelMap")]
+#[pyclass(name = "RustPixelMap", module = "my_library")]
 #[derive(Debug)]
 pub struct RustPixelMap {
-    map: MovingAiMap,
+    /// Walkability as one bit per tile, possibly memory-mapped from a file of to_binary
+    map: BitGrid,
+    /// Optional step weights, searches use plain distances while this is None
+    costs: Option<CostGrid>,
+    /// Row-major free radius around every tile, computed when a unit radius first needs it
+    clearance: LazyClearance,
+    /// Connected walkable areas, updated whenever the map changes
+    components: Components,
+    /// Abstract graph of the last hpa_path call, kept up to date when the map or the weights change
+    hierarchy: Option<Hierarchy>,
+    /// Rectangles changed by the cost and walkability setters, read by RustPlanner to repair its search
+    changes: ChangeLog,
+    /// Statistics the path queries attach to their RustPath
+    search_stats: StatsMode,
+    /// Read-only copy for the queries of path_async, dropped whenever the map changes
+    snapshot: Option<Arc<RustPixelMap>>,
 }
 
 #[pymethods]
 impl RustPixelMap {
     #[new]
-    fn new(width_: usize, height_: usize, map_: Vec<char>) -> Self {
+    fn new(width_: usize, height_: usize, map_: Vec<char>) -> PyResult<Self> {
+        /// Map of width_ * height_ MovingAI tiles like '.' and '@' in row-major order
+        /// Raises ValueError if map_ does not have exactly one tile per cell
+        if map_.len() != width_ * height_ {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Expected {} tiles for a map of size {}x{}, got {}",
+                width_ * height_,
+                width_,
+                height_,
+                map_.len()
+            )));
+        }
+        Ok(RustPixelMap::rust_from_grid(BitGrid::from_tiles(
+            width_, height_, &map_,
+        )))
+    }
+
+    #[staticmethod]
+    #[args(walkable = "vec![1]")]
+    fn from_numpy(array: &PyAny, walkable: Vec<i64>) -> PyResult<Self> {
+        /// Build the map from a 2d numpy array of shape (height, width), e.g. python-sc2's pathing_grid.data_numpy
+        /// Cells whose value is listed in 'walkable' are pathable, all other cells are walls
+        macro_rules! try_dtypes {
+            ($($t:ty),*) => {
+                $(
+                    if let Ok(grid) = array.extract::<PyReadonlyArray2<$t>>() {
+                        let grid = grid.as_array();
+                        let (height, width) = grid.dim();
+                        let map = rust_grid_to_map_chars(grid, &walkable);
+                        return RustPixelMap::new(width, height, map);
+                    }
+                )*
+            };
+        }
+        try_dtypes!(bool, i8, i16, i32, i64, u8, u16, u32, u64);
+        Err(pyo3::exceptions::PyTypeError::new_err(
+            "Expected a 2d numpy array with an integer or bool dtype",
+        ))
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<Self> {
+        /// Load a MovingAI '.map' file, '.', 'G', 'S' and 'W' tiles are walkable and every other tile is a wall
+        /// Raises OSError if the file can not be read and ValueError if it is not a valid map
+        let contents = std::fs::read_to_string(path)?;
+        let (width, height, tiles) =
+            formats::parse_map(&contents).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        RustPixelMap::new(width, height, tiles)
+    }
+
+    #[args(overlays = "None", scale = "4")]
+    fn render_png(&self, path: &str, overlays: Option<&PyList>, scale: usize) -> PyResult<()> {
+        /// Write a picture of the map, walls are dark and walkable cells light, every cell is scale x scale pixels
+        /// overlays are drawn in order, each one of:
+        /// - RustPath: a path, its cells connected by lines, e.g. from find_path
+        /// - PointCollection, a list of RustPoint2, a list of (x, y) tuples or an integer array of shape (N, 2): points,
+        ///   e.g. the corners of any_angle_path, float coordinates are rounded to the closest cell
+        /// - float array of shape (height, width): heatmap from blue to red, e.g. distance_field
+        /// - integer array of shape (height, width): labels in different colors, e.g. components or RustMapAnalysis.labels
+        /// Raises ValueError for points with negative coordinates
+        let mut canvas = Canvas::new(&self.map);
+        if let Some(overlays) = overlays {
+            for (index, overlay) in overlays.iter().enumerate() {
+                let overlay = self.extract_overlay(overlay)?;
+                canvas
+                    .draw(&overlay, render::palette(index))
+                    .map_err(pyo3::exceptions::PyValueError::new_err)?;
+            }
+        }
+        Ok(std::fs::write(path, canvas.to_png(scale))?)
+    }
+
+    #[staticmethod]
+    #[args(flip_y = "false", walkable = "vec![1]")]
+    fn from_text(path: &str, flip_y: bool, walkable: Vec<u8>) -> PyResult<Self> {
+        /// Load a grid of digits like AutomatonLE.txt, see load_grid_text
+        /// Cells whose digit is listed in 'walkable' are pathable, all other cells are walls
+        let contents = std::fs::read_to_string(path)?;
+        let (width, height, values) = formats::parse_grid(&contents, flip_y)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let tiles = values
+            .iter()
+            .map(|value| if walkable.contains(value) { '.' } else { 'O' })
+            .collect();
+        RustPixelMap::new(width, height, tiles)
+    }
+
+    #[args(flip_y = "false")]
+    fn to_text(&self, path: &str, flip_y: bool) -> PyResult<()> {
+        /// Write the map as a grid of digits, 1 for walkable cells and 0 for walls
+        let values: Vec<u8> = grid::tiles(&self.map)
+            .map(|tile| self.map.is_traversable(tile) as u8)
+            .collect();
+        let contents = formats::write_grid(self.map.width(), &values, flip_y)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(std::fs::write(path, contents)?)
+    }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        /// Walkability as one bit per tile plus the weights of set_costs and the cost methods
+        /// The cached graph of hpa_path is not kept and is built again on the next call
+        PyBytes::new(py, &self.rust_state().encode())
+    }
+
+    fn __setstate__(&mut self, state: &PyBytes) -> PyResult<()> {
+        let state =
+            MapState::decode(state.as_bytes()).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let mut pixel_map = RustPixelMap::rust_from_state(state)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        // Planners of this map have to see that everything changed
+        pixel_map.changes = std::mem::take(&mut self.changes);
+        *self = pixel_map;
+        self.everything_changed();
+        Ok(())
+    }
+
+    fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> (&'py PyType, (usize, usize, Vec<char>), &'py PyBytes) {
+        (
+            py.get_type::<RustPixelMap>(),
+            (0, 0, vec![]),
+            self.__getstate__(py),
+        )
+    }
+
+    fn __copy__(&self) -> Self {
+        /// Maps own all of their data, so a copy is always a deep copy
+        self.rust_copy()
+    }
+
+    fn __deepcopy__(&self, _memo: &PyDict) -> Self {
+        self.rust_copy()
+    }
+
+    fn to_binary(&self, path: &str) -> PyResult<()> {
+        /// Write the map in a compact binary format, one bit per tile plus the weights if there are any
+        /// Much faster to load than text or numpy files, see from_binary
+        Ok(std::fs::write(path, self.rust_state().encode())?)
+    }
+
+    #[staticmethod]
+    #[args(mmap = "true")]
+    fn from_binary(path: &str, mmap: bool) -> PyResult<Self> {
+        /// Load a map written by to_binary
+        /// With mmap the tiles are memory-mapped instead of read, the file must not be changed while the map is in use
+        /// The first change of a memory-mapped map copies the tiles into memory, the file is never written to
+        let pixel_map = if mmap {
+            RustPixelMap::rust_from_mapped(MappedFile::open_path(path)?)
+        } else {
+            MapState::decode(&std::fs::read(path)?).and_then(RustPixelMap::rust_from_state)
+        };
+        pixel_map.map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    #[getter]
+    fn memory_mapped(&self) -> bool {
+        /// True while the tiles are read from a memory-mapped file
+        /// Always False on platforms other than unix, which read the whole file instead
+        self.map.is_mapped()
+    }
+
+    #[getter]
+    fn search_stats(&self) -> &'static str {
+        /// "off", "stats" or "trace" - with "stats" every path query fills in the stats of its RustPath,
+        /// "trace" additionally records the expanded tiles in order, which slows the searches down
+        /// Copies keep the mode, pickled maps start with "off"
+        self.search_stats.name()
+    }
+
+    #[setter]
+    fn set_search_stats(&mut self, mode: &str) -> PyResult<()> {
+        let mode = StatsMode::from_str(mode).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.search_stats = mode;
+        self.snapshot = None;
+        Ok(())
+    }
+
+    fn run_scenarios(&self, py: Python, path: &str) -> PyResult<Vec<RustScenarioResult>> {
+        /// Run every scenario of a MovingAI '.scen' file for this map with jps_path, astar_path and find_path with
+        /// algorithm="astar" in 8 directions without corner cutting, which finds paths of the optimal length
+        /// Returns one RustScenarioResult per scenario with the optimal length, the found lengths and the search times
+        /// Only octile_length is comparable to optimal_length: jps_path cuts corners and astar_path moves in 4 directions
+        /// Raises ValueError if a scenario was made for a map of another size
+        let contents = std::fs::read_to_string(path)?;
+        let scenarios =
+            formats::parse_scenarios(&contents).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        if let Some(scenario) = scenarios.iter().find(|scenario| {
+            (scenario.map_width, scenario.map_height) != (self.map.width(), self.map.height())
+        }) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Scenario for a map of size {}x{}, this map has size {}x{}",
+                scenario.map_width,
+                scenario.map_height,
+                self.map.width(),
+                self.map.height()
+            )));
+        }
+        let octile = SearchOptions::default();
+        Ok(py.allow_threads(|| {
+            benchmark::run(
+                &scenarios,
+                |start, goal| self.rust_jps_path(start, goal, 0.0),
+                |start, goal| self.rust_astar_path(start, goal),
+                |start, goal| self.rust_find_path(start, goal, Algorithm::AStar, &octile),
+            )
+        }))
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.map.width()
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.map.height()
+    }
+
+    #[args(raise_not_found = "true", unit_radius = "0.0")]
+    fn jps_path(
+        &self,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        raise_not_found: bool,
+        unit_radius: f64,
+    ) -> PyResult<Option<RustPath>> {
+        /// unit_radius: only cells with at least this clearance are used, see the clearance property
+        /// Raises ValueError if start or goal are outside of the map or unit_radius is negative
+        /// Raises PathNotFoundError if start or goal are walls or not connected, returns None instead with raise_not_found=False
+        check_unit_radius(unit_radius)?;
+        let path = self.rust_jps_path(
+            start_pos.to_coords_2d(),
+            goal_pos.to_coords_2d(),
+            unit_radius,
+        );
+        path_or_none(path, raise_not_found)
+    }
+
+    #[args(
+        raise_not_found = "true",
+        max_expansions = "None",
+        max_distance = "None",
+        unit_radius = "0.0"
+    )]
+    fn astar_path(
+        &self,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        raise_not_found: bool,
+        max_expansions: Option<usize>,
+        max_distance: Option<f64>,
+        unit_radius: f64,
+    ) -> PyResult<Option<RustPath>> {
+        /// Same errors and unit_radius as jps_path
+        /// max_expansions, max_distance: stop after taking this many nodes from the open list or at tiles this far
+        /// from the start, and return the path to the explored tile closest to the goal with reached_goal=False
+        /// A limited search also accepts a blocked goal and only raises if not even the start is walkable
+        check_unit_radius(unit_radius)?;
+        let limits = parse_search_limits(Algorithm::AStar, max_expansions, max_distance)?;
+        let path = self.rust_limited_astar_path(
+            start_pos.to_coords_2d(),
+            goal_pos.to_coords_2d(),
+            unit_radius,
+            &limits,
+        );
+        path_or_none(path, raise_not_found)
+    }
+
+    #[args(
+        cost = "None",
+        heuristic = "None",
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        unit_radius = "0.0",
+        raise_not_found = "true",
+        max_expansions = "None",
+        max_distance = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn astar_path_custom(
+        &self,
+        py: Python,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        cost: Option<&PyAny>,
+        heuristic: Option<&PyAny>,
+        diagonal: bool,
+        corner_cutting: &str,
+        unit_radius: f64,
+        raise_not_found: bool,
+        max_expansions: Option<usize>,
+        max_distance: Option<f64>,
+    ) -> PyResult<Option<RustPath>> {
+        /// A* with rules written in python for one-off cases that do not fit a cost layer, e.g. avoiding a single creep tumor
+        /// cost: called as cost((x, y), (next_x, next_y), base_cost) for every step the search considers and returns the cost
+        /// of the step, base_cost includes the weights of the map, math.inf forbids the step and negative costs raise ValueError
+        /// heuristic: called as heuristic((x, y), (goal_x, goal_y)) for every reached tile and returns the estimated remaining
+        /// cost, the path is only the shortest one if it never overestimates, defaults to octile with diagonal movement and
+        /// manhattan without, negative estimates raise ValueError
+        /// Exceptions of the callbacks stop the search and are raised by this call
+        /// Without callbacks this is find_path with algorithm="astar", which releases the GIL and calls no python at all
+        let (_, options) =
+            parse_search_options("astar", diagonal, corner_cutting, None, unit_radius)?;
+        let limits = parse_search_limits(Algorithm::AStar, max_expansions, max_distance)?;
+        let (start, goal) = (start_pos.to_coords_2d(), goal_pos.to_coords_2d());
+        if cost.is_none() && heuristic.is_none() {
+            let path = py.allow_threads(|| {
+                self.rust_limited_find_path(start, goal, Algorithm::AStar, &options, &limits)
+            });
+            return path_or_none(path, raise_not_found);
+        }
+
+        let step_cost = |from: Coords2D, to: Coords2D, base_cost: f64| -> PyResult<f64> {
+            let cost = match cost {
+                Some(cost) => cost.call1((from, to, base_cost))?.extract::<f64>()?,
+                None => return Ok(base_cost),
+            };
+            if cost.is_nan() || cost < 0.0 {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "cost returned {} for the step from {:?} to {:?}, costs can not be negative",
+                    cost, from, to
+                )));
+            }
+            Ok(cost)
+        };
+        let estimate = |tile: Coords2D| -> PyResult<f64> {
+            let estimate = match heuristic {
+                Some(heuristic) => heuristic.call1((tile, goal))?.extract::<f64>()?,
+                None => return Ok(options.heuristic.distance(tile, goal)),
+            };
+            if estimate.is_nan() || estimate < 0.0 {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "heuristic returned {} for {:?}, estimates can not be negative",
+                    estimate, tile
+                )));
+            }
+            Ok(estimate)
+        };
+        let map = self.view(unit_radius);
+        let mut callback_error = None;
+        let path = self.checked_limited_search(start, goal, unit_radius, &limits, || {
+            let path = with_arena(|arena| {
+                let costs = self.costs.as_ref();
+                pathfinding::astar_by(
+                    arena, &map, costs, start, goal, &options, &limits, step_cost, estimate,
+                )
+            });
+            path.unwrap_or_else(|error| {
+                callback_error = Some(error);
+                SearchResult::not_found(None)
+            })
+        });
+        if let Some(error) = callback_error {
+            return Err(error);
+        }
+        path_or_none(path, raise_not_found)
+    }
+
+    #[args(
+        algorithm = "\"auto\"",
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        heuristic = "None",
+        unit_radius = "0.0",
+        raise_not_found = "true",
+        max_expansions = "None",
+        max_distance = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn find_path(
+        &self,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        algorithm: &str,
+        diagonal: bool,
+        corner_cutting: &str,
+        heuristic: Option<&str>,
+        unit_radius: f64,
+        raise_not_found: bool,
+        max_expansions: Option<usize>,
+        max_distance: Option<f64>,
+    ) -> PyResult<Option<RustPath>> {
+        /// Single entry point for all searches, returns the same RustPath and raises the same errors as jps_path and astar_path
+        /// algorithm: "auto", "astar", "jps" or "bidirectional" - jps is only possible with diagonal=True, corner_cutting="always"
+        /// and heuristic="euclidean" and ignores the cost grid, "auto" falls back to astar while costs are set
+        /// "bidirectional" searches from both ends and gives up early on goals that are cut off from the start
+        /// corner_cutting: "never", "single" or "always"
+        /// heuristic: "manhattan", "octile", "euclidean" or "chebyshev", defaults to octile with diagonal movement and manhattan without
+        /// "manhattan" overestimates diagonal steps and raises ValueError with diagonal=True
+        /// unit_radius: only cells with at least this clearance are used, see the clearance property
+        /// max_expansions, max_distance: limits of astar_path, "auto" uses astar when they are set
+        let (algorithm, options) =
+            parse_search_options(algorithm, diagonal, corner_cutting, heuristic, unit_radius)?;
+        let limits = parse_search_limits(algorithm, max_expansions, max_distance)?;
+        let path = self.rust_limited_find_path(
+            start_pos.to_coords_2d(),
+            goal_pos.to_coords_2d(),
+            algorithm,
+            &options,
+            &limits,
+        );
+        path_or_none(path, raise_not_found)
+    }
+
+    #[args(
+        algorithm = "\"auto\"",
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        heuristic = "None",
+        unit_radius = "0.0",
+        raise_not_found = "true",
+        max_expansions = "None",
+        max_distance = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn path_async(
+        &mut self,
+        py: Python,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        algorithm: &str,
+        diagonal: bool,
+        corner_cutting: &str,
+        heuristic: Option<&str>,
+        unit_radius: f64,
+        raise_not_found: bool,
+        max_expansions: Option<usize>,
+        max_distance: Option<f64>,
+    ) -> PyResult<PyObject> {
+        /// find_path for asyncio, returns a future of the running event loop that resolves to the same result
+        /// The search runs on a worker thread without the GIL and on a snapshot of the map, changes made while the
+        /// query is pending only affect later queries
+        /// Cancelling the future skips the search if no worker has started it yet
+        /// Invalid arguments raise right away, RuntimeError if there is no running event loop
+        let (algorithm, options) =
+            parse_search_options(algorithm, diagonal, corner_cutting, heuristic, unit_radius)?;
+        let limits = parse_search_limits(algorithm, max_expansions, max_distance)?;
+        let (start, goal) = (start_pos.to_coords_2d(), goal_pos.to_coords_2d());
+        self.check_in_bounds(start)?;
+        self.check_in_bounds(goal)?;
+        let snapshot = self.rust_snapshot();
+        tasks::spawn(py, raise_not_found, move || {
+            snapshot.rust_limited_find_path(start, goal, algorithm, &options, &limits)
+        })
+    }
+
+    #[args(
+        cluster_size = "16",
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        heuristic = "None",
+        unit_radius = "0.0",
+        refine = "true",
+        raise_not_found = "true"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn hpa_path(
+        &mut self,
+        py: Python,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        cluster_size: usize,
+        diagonal: bool,
+        corner_cutting: &str,
+        heuristic: Option<&str>,
+        unit_radius: f64,
+        refine: bool,
+        raise_not_found: bool,
+    ) -> PyResult<Option<RustPath>> {
+        /// Hierarchical search for long queries, much faster than find_path on big maps but not always the shortest path
+        /// The map is split into clusters of cluster_size x cluster_size cells, the abstract graph is built on the first
+        /// call and kept until cluster_size or the movement options change, set_blocked / set_walkable and cost
+        /// changes only rebuild the clusters they touch
+        /// With refine=False only start, goal and the cells where the path crosses cluster borders are returned
+        /// The other arguments and the errors are the same as in find_path
+        if cluster_size == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "cluster_size has to be at least 1",
+            ));
+        }
+        let (_, options) =
+            parse_search_options("astar", diagonal, corner_cutting, heuristic, unit_radius)?;
+        let (start, goal) = (start_pos.to_coords_2d(), goal_pos.to_coords_2d());
+        let path =
+            py.allow_threads(|| self.rust_hpa_path(start, goal, cluster_size, &options, refine));
+        path_or_none(path, raise_not_found)
+    }
+
+    fn line_of_sight(&self, a: &PyAny, b: &PyAny) -> PyResult<bool> {
+        /// True if every cell touched by the straight line from a to b is walkable
+        /// a and b are RustPoint2 or (x, y) tuples of floats, cell (x, y) covers x - 0.5 to x + 0.5
+        let (a, b) = (extract_point(a)?, extract_point(b)?);
+        Ok(line_of_sight::line_of_sight(&self.map, a, b))
+    }
+
+    fn line_of_sight_many<'py>(
+        &self,
+        py: Python<'py>,
+        starts: &PyAny,
+        ends: &PyAny,
+    ) -> PyResult<&'py PyArray1<bool>> {
+        /// line_of_sight for many segments at once without holding the GIL
+        /// starts and ends are numpy arrays of shape (N, 2) or lists of points, returns a bool array of shape (N,)
+        let starts = extract_points(starts)?;
+        let ends = extract_points(ends)?;
+        if starts.len() != ends.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Got {} starts but {} ends",
+                starts.len(),
+                ends.len()
+            )));
+        }
+        let visible: Vec<bool> = py.allow_threads(|| {
+            starts
+                .par_iter()
+                .zip(ends.par_iter())
+                .map(|(&a, &b)| line_of_sight::line_of_sight(&self.map, a, b))
+                .collect()
+        });
+        Ok(Array1::from(visible).into_pyarray(py))
+    }
+
+    fn raycast(
+        &self,
+        origin: &PyAny,
+        direction: (f64, f64),
+        max_dist: f64,
+    ) -> PyResult<Option<(f64, f64)>> {
+        /// Point where the ray from origin first touches a wall or the map border
+        /// Returns None if nothing is hit within max_dist
+        /// Raises ValueError for a direction of (0, 0) or a negative max_dist
+        if direction.0 == 0.0 && direction.1 == 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "direction can not be (0, 0)",
+            ));
+        }
+        if max_dist.is_nan() || max_dist < 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "max_dist can not be negative, got {}",
+                max_dist
+            )));
+        }
+        let origin = extract_point(origin)?;
+        Ok(line_of_sight::raycast(
+            &self.map, origin, direction, max_dist,
+        ))
+    }
+
+    #[args(unit_radius = "0.0")]
+    fn smooth_path(&self, path: PyRef<RustPath>, unit_radius: f64) -> Vec<(f64, f64)> {
+        /// Any-angle version of a path: only the waypoints where the direction changes are kept,
+        /// the straight segments between them do not touch any wall (or cell with less clearance than unit_radius)
+        /// Paths that cut corners, e.g. from jps_path, get an extra point next to every corner they cut, only
+        /// diagonal steps between two walls (corner_cutting="always") are kept although they touch the walls
+        line_of_sight::smooth(&self.view(unit_radius), &path.waypoints)
+    }
+
+    #[args(
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        heuristic = "None",
+        unit_radius = "0.0",
+        raise_not_found = "true"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn any_angle_path(
+        &self,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        diagonal: bool,
+        corner_cutting: &str,
+        heuristic: Option<&str>,
+        unit_radius: f64,
+        raise_not_found: bool,
+    ) -> PyResult<Option<Vec<(f64, f64)>>> {
+        /// find_path followed by smooth_path, returns the corner points as floats from start to goal
+        let (algorithm, options) =
+            parse_search_options("auto", diagonal, corner_cutting, heuristic, unit_radius)?;
+        let path = self.rust_find_path(
+            start_pos.to_coords_2d(),
+            goal_pos.to_coords_2d(),
+            algorithm,
+            &options,
+        );
+        let path = path_or_none(path, raise_not_found)?;
+        Ok(path.map(|path| line_of_sight::smooth(&self.view(unit_radius), &path.waypoints)))
+    }
+
+    #[args(
+        algorithm = "\"auto\"",
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        heuristic = "None",
+        unit_radius = "0.0",
+        max_expansions = "None",
+        max_distance = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn batch_paths(
+        &self,
+        py: Python,
+        starts: &PyAny,
+        goals: &PyAny,
+        algorithm: &str,
+        diagonal: bool,
+        corner_cutting: &str,
+        heuristic: Option<&str>,
+        unit_radius: f64,
+        max_expansions: Option<usize>,
+        max_distance: Option<f64>,
+    ) -> PyResult<Vec<RustPath>> {
+        /// Runs find_path for each (start, goal) pair on a thread pool without holding the GIL
+        /// starts and goals are lists of RustPoint2 or numpy arrays of shape (N, 2), results are in input order
+        /// Raises ValueError if any point is outside of the map, failed queries have reached_goal=False instead of raising
+        let (algorithm, options) =
+            parse_search_options(algorithm, diagonal, corner_cutting, heuristic, unit_radius)?;
+        let limits = parse_search_limits(algorithm, max_expansions, max_distance)?;
+        let starts = extract_coords(starts)?;
+        let goals = extract_coords(goals)?;
+        if starts.len() != goals.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Got {} starts but {} goals",
+                starts.len(),
+                goals.len()
+            )));
+        }
+        for &point in starts.iter().chain(goals.iter()) {
+            self.check_in_bounds(point)?;
+        }
+        Ok(py
+            .allow_threads(|| self.rust_batch_paths(&starts, &goals, algorithm, &options, &limits)))
+    }
+
+    #[args(diagonal = "true", corner_cutting = "\"never\"", unit_radius = "0.0")]
+    fn distance_field<'py>(
+        &self,
+        py: Python<'py>,
+        sources: &PyAny,
+        diagonal: bool,
+        corner_cutting: &str,
+        unit_radius: f64,
+    ) -> PyResult<&'py PyArray2<f64>> {
+        /// Walking distance from the nearest source for every cell, inf for walls and unreachable cells
+        /// With costs it is the weighted cost of walking from the cell to its nearest source, like find_path
+        /// sources is a list of RustPoint2 or a numpy array of shape (N, 2)
+        /// Returns an array of shape (height, width), same layout as the array passed to from_numpy
+        let (_, options) =
+            parse_search_options("auto", diagonal, corner_cutting, None, unit_radius)?;
+        let sources = extract_coords(sources)?;
+        for &source in sources.iter() {
+            self.check_in_bounds(source)?;
+        }
+        let field = py.allow_threads(|| self.rust_distance_field(&sources, &options));
+        Ok(field.into_pyarray(py))
+    }
+
+    #[args(diagonal = "true", corner_cutting = "\"never\"", unit_radius = "0.0")]
+    fn flow_field<'py>(
+        &self,
+        py: Python<'py>,
+        goal_pos: RustPoint2,
+        diagonal: bool,
+        corner_cutting: &str,
+        unit_radius: f64,
+    ) -> PyResult<&'py PyArray3<i8>> {
+        /// Direction (dx, dy) of the next step on a shortest path to the goal for every cell, the cheapest one with costs
+        /// Returns an array of shape (height, width, 2), (0, 0) marks the goal, walls and unreachable cells
+        let (_, options) =
+            parse_search_options("auto", diagonal, corner_cutting, None, unit_radius)?;
+        let goal = goal_pos.to_coords_2d();
+        self.check_in_bounds(goal)?;
+        let field = py.allow_threads(|| self.rust_flow_field(goal, &options));
+        Ok(field.into_pyarray(py))
+    }
+
+    #[getter]
+    fn clearance<'py>(&self, py: Python<'py>) -> &'py PyArray2<f64> {
+        /// Free radius around every cell as an array of shape (height, width): distance from the cell center
+        /// to the closest wall minus half a cell, 0 for walls, everything outside of the map counts as wall
+        Array2::from_shape_vec(
+            (self.map.height(), self.map.width()),
+            self.rust_clearance().to_vec(),
+        )
+        .unwrap()
+        .into_pyarray(py)
+    }
+
+    #[getter]
+    fn costs<'py>(&self, py: Python<'py>) -> &'py PyArray2<f64> {
+        /// Copy of the step weights as an array of shape (height, width), all ones if no costs are set
+        let weights = match &self.costs {
+            Some(costs) => costs.weights().to_vec(),
+            None => vec![CostGrid::MIN_WEIGHT; self.map.width() * self.map.height()],
+        };
+        Array2::from_shape_vec((self.map.height(), self.map.width()), weights)
+            .unwrap()
+            .into_pyarray(py)
+    }
+
+    fn set_costs(&mut self, array: PyReadonlyArray2<f64>) -> PyResult<()> {
+        /// Replace the step weights with a float array of shape (height, width), weights have to be at least 1
+        /// Each step then costs its base distance multiplied by the weight of the entered cell
+        let array = array.as_array();
+        if array.dim() != (self.map.height(), self.map.width()) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Expected an array of shape ({}, {}), got {:?}",
+                self.map.height(),
+                self.map.width(),
+                array.dim()
+            )));
+        }
+        let weights = array.iter().cloned().collect();
+        let costs = CostGrid::from_weights(self.map.width(), self.map.height(), weights)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.costs = Some(costs);
+        self.hierarchy = None;
+        self.snapshot = None;
+        self.everything_changed();
+        Ok(())
+    }
+
+    fn clear_costs(&mut self) {
+        /// Remove all step weights, searches use plain distances again
+        self.costs.take();
+        self.hierarchy = None;
+        self.snapshot = None;
+        self.everything_changed();
+    }
+
+    fn set_cost(&mut self, pos: RustPoint2, weight: f64) -> PyResult<()> {
+        /// Change the weight of a single cell
+        let pos = pos.to_coords_2d();
+        self.cost_grid_mut()
+            .set_weight(pos, weight)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.changed(pos, pos, 0.0);
+        Ok(())
+    }
+
+    #[args(falloff = "\"linear\"")]
+    fn add_cost_circle(
+        &mut self,
+        center: (f64, f64),
+        radius: f64,
+        weight: f64,
+        falloff: &str,
+    ) -> PyResult<()> {
+        /// Add 'weight' to all cells within 'radius' of 'center', e.g. the range of an enemy unit
+        /// falloff: "constant", "linear" or "quadratic" - how the added weight decreases towards the edge
+        let falloff = parse_falloff(radius, weight, falloff)?;
+        self.cost_grid_mut()
+            .add_circle(center, radius, weight, falloff);
+        self.circle_changed(center, radius);
+        Ok(())
+    }
+
+    #[args(falloff = "\"linear\"")]
+    fn remove_cost_circle(
+        &mut self,
+        center: (f64, f64),
+        radius: f64,
+        weight: f64,
+        falloff: &str,
+    ) -> PyResult<()> {
+        /// Undo add_cost_circle called with the same arguments, weights never drop below 1
+        let falloff = parse_falloff(radius, weight, falloff)?;
+        self.cost_grid_mut()
+            .add_circle(center, radius, -weight, falloff);
+        self.circle_changed(center, radius);
+        Ok(())
+    }
+
+    #[args(w = "1", h = "1")]
+    fn set_blocked(&mut self, x: usize, y: usize, w: usize, h: usize) -> PyResult<()> {
+        /// Turn the rectangle with top left corner (x, y), width w and height h into walls, e.g. a new building
+        /// Raises ValueError if the rectangle does not fit into the map
+        Ok(self.rust_set_walkable((x, y), (w, h), false)?)
+    }
+
+    #[args(w = "1", h = "1")]
+    fn set_walkable(&mut self, x: usize, y: usize, w: usize, h: usize) -> PyResult<()> {
+        /// Make the rectangle walkable again, e.g. after a building was destroyed
+        Ok(self.rust_set_walkable((x, y), (w, h), true)?)
+    }
+
+    #[args(merge_ratio = "0.7")]
+    fn analyze(&self, py: Python, merge_ratio: f64) -> PyResult<RustMapAnalysis> {
+        /// Split the walkable area into regions and the chokes between them
+        /// Neighboring regions merge if the passage between them is at least merge_ratio times as wide as the
+        /// open space of the smaller region, lower values give fewer and larger regions
+        if !(0.0..=1.0).contains(&merge_ratio) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "merge_ratio has to be between 0 and 1, got {}",
+                merge_ratio
+            )));
+        }
+        Ok(
+            py.allow_threads(|| {
+                RustMapAnalysis::new(&self.map, self.rust_clearance(), merge_ratio)
+            }),
+        )
+    }
+
+    fn components<'py>(&self, py: Python<'py>) -> &'py PyArray2<i32> {
+        /// Label of the connected walkable area of every cell as an array of shape (height, width), -1 for walls
+        /// Cells are connected through their 4 direct neighbors, areas are numbered from 0 in row-major order
+        /// set_blocked and set_walkable only relabel the areas they touch: merged areas keep the lowest label, a
+        /// split area keeps its label for one part, and labels of areas that are gone are reused for new ones
+        Array2::from_shape_vec(
+            (self.map.height(), self.map.width()),
+            self.components.labels(),
+        )
+        .unwrap()
+        .into_pyarray(py)
+    }
+
+    #[getter]
+    fn component_count(&self) -> usize {
+        self.components.count()
+    }
+
+    fn component_of(&self, point: RustPoint2) -> PyResult<Option<usize>> {
+        /// Label of the area containing the point, None for walls
+        let point = point.to_coords_2d();
+        self.check_in_bounds(point)?;
+        Ok(self.components.component_of(point))
+    }
+
+    fn is_reachable(&self, a: RustPoint2, b: RustPoint2) -> PyResult<bool> {
+        /// True if a path between the two points exists, without running a search
+        /// Only matches searches that do not cut corners: 4-way searches and corner_cutting "never" or "single"
+        /// with unit_radius=0. jps_path and corner_cutting="always" can step diagonally between two walls, which
+        /// connects areas that this reports as separate
+        /// Raises ValueError for points outside of the map
+        let (a, b) = (a.to_coords_2d(), b.to_coords_2d());
+        self.check_in_bounds(a)?;
+        self.check_in_bounds(b)?;
+        Ok(self.components.is_reachable(a, b))
+    }
+}
+
+fn path_or_none(
+    path: Result<RustPath, PathError>,
+    raise_not_found: bool,
+) -> PyResult<Option<RustPath>> {
+    match path {
+        Ok(path) => Ok(Some(path)),
+        Err(error @ PathError::OutOfBounds { .. }) => Err(error.into()),
+        Err(_) if !raise_not_found => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn parse_search_limits(
+    algorithm: Algorithm,
+    max_expansions: Option<usize>,
+    max_distance: Option<f64>,
+) -> PyResult<SearchLimits> {
+    if max_distance.is_some_and(|distance| distance.is_nan() || distance < 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "max_distance can not be negative",
+        ));
+    }
+    let limits = SearchLimits {
+        max_expansions,
+        max_distance,
+    };
+    if limits.is_limited() && matches!(algorithm, Algorithm::Jps | Algorithm::Bidirectional) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "max_expansions and max_distance are only supported by astar",
+        ));
+    }
+    Ok(limits)
+}
+
+fn parse_falloff(radius: f64, weight: f64, falloff: &str) -> PyResult<Falloff> {
+    if radius.is_nan() || weight.is_nan() || radius < 0.0 || weight < 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "radius and weight can not be negative",
+        ));
+    }
+    Falloff::from_str(falloff).map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+fn check_unit_radius(unit_radius: f64) -> PyResult<()> {
+    if unit_radius.is_nan() || unit_radius < 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "unit_radius can not be negative",
+        ));
+    }
+    Ok(())
+}
+
+fn parse_search_options(
+    algorithm: &str,
+    diagonal: bool,
+    corner_cutting: &str,
+    heuristic: Option<&str>,
+    unit_radius: f64,
+) -> PyResult<(Algorithm, SearchOptions)> {
+    let to_value_error = pyo3::exceptions::PyValueError::new_err;
+    let algorithm = Algorithm::from_str(algorithm).map_err(to_value_error)?;
+    let heuristic = match heuristic {
+        Some(name) => Heuristic::from_str(name).map_err(to_value_error)?,
+        None if diagonal => Heuristic::Octile,
+        None => Heuristic::Manhattan,
+    };
+    let options = SearchOptions {
+        diagonal,
+        corner_cutting: CornerCutting::from_str(corner_cutting).map_err(to_value_error)?,
+        heuristic,
+        unit_radius,
+    };
+    check_unit_radius(unit_radius)?;
+    // A diagonal step costs sqrt(2) but manhattan counts it as 2, the paths would not be the shortest ones and
+    // bidirectional search could not tell when to stop
+    if diagonal && heuristic == Heuristic::Manhattan {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "heuristic=\"manhattan\" overestimates diagonal steps, use it with diagonal=False",
+        ));
+    }
+    if algorithm == Algorithm::Jps && !options.supports_jps() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "jps requires diagonal=True, corner_cutting=\"always\" and heuristic=\"euclidean\"",
+        ));
+    }
+    Ok((algorithm, options))
+}
+
+fn extract_point(point: &PyAny) -> PyResult<(f64, f64)> {
+    /// Accepts a RustPoint2 or an (x, y) tuple
+    if let Ok(point) = point.extract::<RustPoint2>() {
+        return Ok((point.x as f64, point.y as f64));
+    }
+    point.extract::<(f64, f64)>()
+}
+
+fn extract_points(points: &PyAny) -> PyResult<Vec<(f64, f64)>> {
+    /// Accepts a float numpy array of shape (N, 2), a list of (x, y) tuples or anything extract_coords accepts
+    macro_rules! try_dtypes {
+        ($($t:ty),*) => {
+            $(
+                if let Ok(array) = points.extract::<PyReadonlyArray2<$t>>() {
+                    let array = array.as_array();
+                    if array.ncols() != 2 {
+                        return Err(pyo3::exceptions::PyValueError::new_err(
+                            "Expected a numpy array of shape (N, 2)",
+                        ));
+                    }
+                    return Ok(array
+                        .outer_iter()
+                        .map(|row| (f64::from(row[0]), f64::from(row[1])))
+                        .collect());
+                }
+            )*
+        };
+    }
+    try_dtypes!(f64, f32);
+    if let Ok(points) = points.extract::<Vec<(f64, f64)>>() {
+        return Ok(points);
+    }
+    let coords = extract_coords(points)?;
+    Ok(coords.iter().map(|&(x, y)| (x as f64, y as f64)).collect())
+}
+
+fn extract_coords(points: &PyAny) -> PyResult<Vec<Coords2D>> {
+    /// Accepts a list of RustPoint2 or an integer numpy array of shape (N, 2) with x in the first column
+    if let Ok(points) = points.extract::<Vec<RustPoint2>>() {
+        return Ok(points.iter().map(|p| p.to_coords_2d()).collect());
+    }
+    macro_rules! try_dtypes {
+        ($($t:ty),*) => {
+            $(
+                if let Ok(array) = points.extract::<PyReadonlyArray2<$t>>() {
+                    let array = array.as_array();
+                    if array.ncols() != 2 {
+                        return Err(pyo3::exceptions::PyValueError::new_err(
+                            "Expected a numpy array of shape (N, 2)",
+                        ));
+                    }
+                    return array
+                        .outer_iter()
+                        .map(|row| {
+                            let (x, y): (i128, i128) = (row[0].into(), row[1].into());
+                            if x < 0 || y < 0 {
+                                return Err(pyo3::exceptions::PyValueError::new_err(
+                                    "Coordinates can not be negative",
+                                ));
+                            }
+                            Ok((x as usize, y as usize))
+                        })
+                        .collect();
+                }
+            )*
+        };
+    }
+    try_dtypes!(i64, i32, i16, i8, u64, u32, u16, u8);
+    Err(pyo3::exceptions::PyTypeError::new_err(
+        "Expected a list of RustPoint2 or an integer numpy array of shape (N, 2)",
+    ))
+}
+
+impl RustPixelMap {
+    fn cost_grid_mut(&mut self) -> &mut CostGrid {
+        let (width, height) = (self.map.width(), self.map.height());
+        self.snapshot = None;
+        self.costs
+            .get_or_insert_with(|| CostGrid::new(width, height))
+    }
+
+    fn check_in_bounds(&self, point: Coords2D) -> Result<(), PathError> {
+        if self.map.is_out_of_bound(point) {
+            return Err(PathError::OutOfBounds {
+                point,
+                width: self.map.width(),
+                height: self.map.height(),
+            });
+        }
+        Ok(())
+    }
+
+    fn rust_set_walkable(
+        &mut self,
+        corner: Coords2D,
+        size: (usize, usize),
+        walkable: bool,
+    ) -> Result<(), PathError> {
+        if size.0 == 0 || size.1 == 0 {
+            return Ok(());
+        }
+        let far_corner = (
+            corner.0.saturating_add(size.0 - 1),
+            corner.1.saturating_add(size.1 - 1),
+        );
+        self.check_in_bounds(corner)?;
+        self.check_in_bounds(far_corner)?;
+        self.snapshot = None;
+        for y in corner.1..=far_corner.1 {
+            for x in corner.0..=far_corner.0 {
+                self.map.set((x, y), walkable);
+            }
+        }
+        let changed =
+            (corner.1..=far_corner.1).flat_map(|y| (corner.0..=far_corner.0).map(move |x| (x, y)));
+        if walkable {
+            self.clearance.open();
+            self.components.open(&self.map, changed);
+        } else {
+            self.clearance.block(&self.map, corner, far_corner);
+            self.components.block(&self.map, changed);
+        }
+        self.changed(corner, far_corner, 1.0);
+        Ok(())
+    }
+
+    fn rust_from_grid(map: BitGrid) -> Self {
         RustPixelMap {
-            map: MovingAiMap::new(String::from("test"), width_, height_, map_),
+            clearance: LazyClearance::default(),
+            components: Components::new(&map),
+            map,
+            costs: None,
+            hierarchy: None,
+            changes: ChangeLog::default(),
+            search_stats: StatsMode::Off,
+            snapshot: None,
+        }
+    }
+
+    fn rust_copy(&self) -> Self {
+        RustPixelMap {
+            map: self.map.clone(),
+            costs: self.costs.clone(),
+            clearance: self.clearance.clone(),
+            components: self.components.clone(),
+            hierarchy: self.hierarchy.clone(),
+            changes: self.changes.clone(),
+            search_stats: self.search_stats,
+            snapshot: self.snapshot.clone(),
+        }
+    }
+
+    fn rust_state(&self) -> MapState {
+        MapState {
+            width: self.map.width(),
+            height: self.map.height(),
+            walkable: self.map.bytes().to_vec(),
+            weights: self.costs.as_ref().map(|costs| costs.weights().to_vec()),
+        }
+    }
+
+    fn rust_from_state(state: MapState) -> Result<Self, String> {
+        let map = BitGrid::from_bytes(state.width, state.height, state.walkable)?;
+        let mut pixel_map = RustPixelMap::rust_from_grid(map);
+        if let Some(weights) = state.weights {
+            pixel_map.costs = Some(CostGrid::from_weights(state.width, state.height, weights)?);
+        }
+        Ok(pixel_map)
+    }
+
+    /// Map of a file of to_binary, the walkability is used straight from the mapped file
+    fn rust_from_mapped(file: MappedFile) -> Result<Self, String> {
+        let layout = MapLayout::read(file.bytes())?;
+        let map = BitGrid::mapped(layout.width, layout.height, file, layout.walkable)?;
+        let mut pixel_map = RustPixelMap::rust_from_grid(map);
+        if let Some(weights) = layout.weights {
+            pixel_map.costs = Some(CostGrid::from_weights(
+                layout.width,
+                layout.height,
+                weights,
+            )?);
+        }
+        Ok(pixel_map)
+    }
+
+    fn extract_overlay(&self, overlay: &PyAny) -> PyResult<Overlay> {
+        let shape = (self.map.height(), self.map.width());
+        if let Ok(path) = overlay.extract::<PyRef<RustPath>>() {
+            return Ok(Overlay::Path(path.waypoints.clone()));
+        }
+        if let Ok(collection) = overlay.extract::<PyRef<PointCollection>>() {
+            let points = collection.points.iter().map(|p| p.to_coords_2d()).collect();
+            return Ok(Overlay::Points(points));
+        }
+        if let Ok(grid) = overlay.extract::<PyReadonlyArray2<f64>>() {
+            if grid.shape() == [shape.0, shape.1] {
+                return Ok(Overlay::Heatmap(grid.as_array().iter().cloned().collect()));
+            }
+        }
+        macro_rules! try_dtypes {
+            ($($t:ty),*) => {
+                $(
+                    if let Ok(grid) = overlay.extract::<PyReadonlyArray2<$t>>() {
+                        if grid.shape() == [shape.0, shape.1] {
+                            let labels = grid.as_array().iter().map(|&label| label as i32).collect();
+                            return Ok(Overlay::Labels(labels));
+                        }
+                    }
+                )*
+            };
+        }
+        try_dtypes!(i32, i64);
+        // Only a RustPath is known to be a connected path, tuples are drawn as the cells they fall into
+        if let Ok(points) = overlay.extract::<Vec<(f64, f64)>>() {
+            return points
+                .iter()
+                .map(|&(x, y)| {
+                    if !(x >= 0.0 && y >= 0.0) {
+                        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                            "Coordinates can not be negative, got ({}, {})",
+                            x, y
+                        )));
+                    }
+                    Ok((x.round() as usize, y.round() as usize))
+                })
+                .collect::<PyResult<_>>()
+                .map(Overlay::Points);
+        }
+        Ok(Overlay::Points(extract_coords(overlay)?))
+    }
+
+    fn changed(&mut self, corner: Coords2D, far_corner: Coords2D, reach: f64) {
+        /// Records a changed rectangle for the planners and updates the cached hierarchy
+        self.changes.push(corner, far_corner, reach);
+        self.update_hierarchy(corner, far_corner, reach);
+    }
+
+    fn everything_changed(&mut self) {
+        let (width, height) = (self.map.width(), self.map.height());
+        if width > 0 && height > 0 {
+            self.changes.push((0, 0), (width - 1, height - 1), 0.0);
+        }
+    }
+
+    fn update_hierarchy(&mut self, corner: Coords2D, far_corner: Coords2D, reach: f64) {
+        /// Rebuilds the clusters of the cached hierarchy around a changed rectangle
+        /// Changed walls also change the clearance up to the unit radius around them, 'reach' is 0 for weight changes
+        let hierarchy = match &mut self.hierarchy {
+            Some(hierarchy) => hierarchy,
+            None => return,
+        };
+        let margin = (reach * (hierarchy.options().unit_radius + 0.5)).ceil() as usize;
+        let corner = (
+            corner.0.saturating_sub(margin),
+            corner.1.saturating_sub(margin),
+        );
+        let far_corner = (far_corner.0 + margin, far_corner.1 + margin);
+        let map = self
+            .clearance
+            .view(&self.map, hierarchy.options().unit_radius);
+        hierarchy.update(&map, self.costs.as_ref(), corner, far_corner);
+    }
+
+    fn circle_changed(&mut self, center: (f64, f64), radius: f64) {
+        let (width, height) = (self.map.width(), self.map.height());
+        if width == 0 || height == 0 {
+            return;
         }
+        let clamp = |value: f64, size: usize| (value.max(0.0) as usize).min(size - 1);
+        let corner = (
+            clamp((center.0 - radius).floor(), width),
+            clamp((center.1 - radius).floor(), height),
+        );
+        let far_corner = (
+            clamp((center.0 + radius).ceil(), width),
+            clamp((center.1 + radius).ceil(), height),
+        );
+        self.changed(corner, far_corner, 0.0);
     }
 
-    fn jps_path(&self, start_pos: RustPoint2, goal_pos: RustPoint2) -> Vec<Coords2D> {
-        if let Some(path) = jps_path(&self.map, start_pos.to_coords_2d(), goal_pos.to_coords_2d()) {
-            return path.steps();
+    fn rust_clearance(&self) -> &[f64] {
+        self.clearance.get(&self.map)
+    }
+
+    fn view(&self, unit_radius: f64) -> ClearanceMap<'_, BitGrid> {
+        /// The map as seen by a unit of the given radius
+        self.clearance.view(&self.map, unit_radius)
+    }
+
+    fn rust_plan(&self, planner: &mut DStarLite) -> Result<RustPath, PathError> {
+        planner.plan(
+            &self.view(planner.unit_radius()),
+            self.costs.as_ref(),
+            &self.changes,
+        )
+    }
+
+    fn checked_search<F: FnOnce() -> SearchResult>(
+        &self,
+        start: Coords2D,
+        goal: Coords2D,
+        unit_radius: f64,
+        search: F,
+    ) -> Result<RustPath, PathError> {
+        /// Runs the search only if both endpoints are inside of the map and walkable for the unit
+        self.checked_limited_search(start, goal, unit_radius, &SearchLimits::default(), search)
+    }
+
+    fn checked_limited_search<F: FnOnce() -> SearchResult>(
+        &self,
+        start: Coords2D,
+        goal: Coords2D,
+        unit_radius: f64,
+        limits: &SearchLimits,
+        search: F,
+    ) -> Result<RustPath, PathError> {
+        /// Limited searches also accept a blocked goal and return their partial path instead of failing
+        self.check_in_bounds(start)?;
+        self.check_in_bounds(goal)?;
+        let map = self.view(unit_radius);
+        let endpoints = if limits.is_limited() {
+            vec![start]
+        } else {
+            vec![start, goal]
+        };
+        for &point in endpoints.iter() {
+            if !map.is_traversable(point) {
+                return Err(PathError::Blocked(point));
+            }
+        }
+        let path = self.rust_run_search(search);
+        if !path.reached_goal && path.waypoints.is_empty() {
+            return Err(PathError::NotFound { start, goal });
         }
-        vec![]
+        Ok(path)
     }
 
-    fn astar_path(&self, start_pos: RustPoint2, goal_pos: RustPoint2) -> Vec<Coords2D> {
-        if let Some(path) =
-            a_star_path(&self.map, start_pos.to_coords_2d(), goal_pos.to_coords_2d())
-        {
-            return path.steps();
+    fn rust_snapshot(&mut self) -> Arc<RustPixelMap> {
+        /// Everything the searches of find_path read, shared by all queries until the map changes
+        if let Some(snapshot) = &self.snapshot {
+            return snapshot.clone();
         }
-        vec![]
+        let snapshot = Arc::new(RustPixelMap {
+            map: self.map.clone(),
+            costs: self.costs.clone(),
+            clearance: self.clearance.clone(),
+            components: self.components.clone(),
+            hierarchy: None,
+            changes: ChangeLog::default(),
+            search_stats: self.search_stats,
+            snapshot: None,
+        });
+        self.snapshot = Some(snapshot.clone());
+        snapshot
+    }
+
+    fn rust_run_search<F: FnOnce() -> SearchResult>(&self, search: F) -> RustPath {
+        if self.search_stats == StatsMode::Off {
+            return search().into();
+        }
+        // The searches leave their counters in the arena of the thread they ran on
+        with_arena(|arena| {
+            arena.counters = SearchCounters::new(self.search_stats == StatsMode::Trace)
+        });
+        let started = Instant::now();
+        let mut path = RustPath::from(search());
+        let seconds = started.elapsed().as_secs_f64();
+        let counters = with_arena(|arena| std::mem::take(&mut arena.counters));
+        path.stats = Some(RustSearchStats {
+            nodes_expanded: counters.expanded,
+            nodes_generated: counters.generated,
+            open_list_peak: counters.open_peak,
+            seconds,
+            trace: if counters.tracing {
+                Some(counters.trace)
+            } else {
+                None
+            },
+        });
+        path
+    }
+
+    fn rust_jps_path(
+        &self,
+        start: Coords2D,
+        goal: Coords2D,
+        unit_radius: f64,
+    ) -> Result<RustPath, PathError> {
+        self.checked_search(start, goal, unit_radius, || {
+            jps::jps(&self.view(unit_radius), start, goal)
+        })
+    }
+
+    fn rust_astar_path(&self, start: Coords2D, goal: Coords2D) -> Result<RustPath, PathError> {
+        self.rust_limited_astar_path(start, goal, 0.0, &SearchLimits::default())
+    }
+
+    fn rust_limited_astar_path(
+        &self,
+        start: Coords2D,
+        goal: Coords2D,
+        unit_radius: f64,
+        limits: &SearchLimits,
+    ) -> Result<RustPath, PathError> {
+        // blitz-path's A* moves in 4 directions, but its neighbour lookup underflows next to the map border
+        let options = SearchOptions {
+            diagonal: false,
+            corner_cutting: CornerCutting::Never,
+            heuristic: Heuristic::Manhattan,
+            unit_radius,
+        };
+        self.checked_limited_search(start, goal, unit_radius, limits, || {
+            pathfinding::astar(&self.view(unit_radius), None, start, goal, &options, limits)
+        })
+    }
+
+    fn rust_find_path(
+        &self,
+        start: Coords2D,
+        goal: Coords2D,
+        algorithm: Algorithm,
+        options: &SearchOptions,
+    ) -> Result<RustPath, PathError> {
+        self.rust_limited_find_path(start, goal, algorithm, options, &SearchLimits::default())
+    }
+
+    fn rust_limited_find_path(
+        &self,
+        start: Coords2D,
+        goal: Coords2D,
+        algorithm: Algorithm,
+        options: &SearchOptions,
+        limits: &SearchLimits,
+    ) -> Result<RustPath, PathError> {
+        // Only A* can stop at the limits with a partial path
+        let algorithm = match algorithm {
+            Algorithm::Auto if limits.is_limited() => Algorithm::AStar,
+            Algorithm::Auto if options.supports_jps() && self.costs.is_none() => Algorithm::Jps,
+            Algorithm::Auto => Algorithm::AStar,
+            algorithm => algorithm,
+        };
+        let map = self.view(options.unit_radius);
+        let costs = self.costs.as_ref();
+        self.checked_limited_search(
+            start,
+            goal,
+            options.unit_radius,
+            limits,
+            || match algorithm {
+                Algorithm::Jps => jps::jps(&map, start, goal),
+                Algorithm::Bidirectional => {
+                    pathfinding::bidirectional_astar(&map, costs, start, goal, options)
+                }
+                _ => pathfinding::astar(&map, costs, start, goal, options, limits),
+            },
+        )
+    }
+
+    fn rust_hpa_path(
+        &mut self,
+        start: Coords2D,
+        goal: Coords2D,
+        cluster_size: usize,
+        options: &SearchOptions,
+        refine: bool,
+    ) -> Result<RustPath, PathError> {
+        self.check_in_bounds(start)?;
+        self.check_in_bounds(goal)?;
+        let outdated = match &self.hierarchy {
+            Some(hierarchy) => {
+                hierarchy.cluster_size() != cluster_size || hierarchy.options() != options
+            }
+            None => true,
+        };
+        if outdated {
+            let map = self.view(options.unit_radius);
+            let hierarchy = Hierarchy::new(&map, self.costs.as_ref(), cluster_size, *options);
+            self.hierarchy = Some(hierarchy);
+        }
+        let hierarchy = self.hierarchy.as_ref().unwrap();
+        let map = self.view(options.unit_radius);
+        self.checked_search(start, goal, options.unit_radius, || {
+            hierarchy.find_path(&map, self.costs.as_ref(), start, goal, refine)
+        })
+    }
+
+    fn rust_distance_field(&self, sources: &[Coords2D], options: &SearchOptions) -> Array2<f64> {
+        let map = self.view(options.unit_radius);
+        let field = pathfinding::dijkstra(&map, self.costs.as_ref(), sources, options);
+        Array2::from_shape_vec((self.map.height(), self.map.width()), field.distances).unwrap()
+    }
+
+    fn rust_flow_field(&self, goal: Coords2D, options: &SearchOptions) -> Array3<i8> {
+        let width = self.map.width();
+        let map = self.view(options.unit_radius);
+        let field = pathfinding::dijkstra(&map, self.costs.as_ref(), &[goal], options);
+        let mut directions = Array3::zeros((self.map.height(), width, 2));
+        for (index, &parent) in field.parents.iter().enumerate() {
+            if parent == usize::MAX {
+                continue;
+            }
+            let (x, y) = (index % width, index / width);
+            directions[[y, x, 0]] = ((parent % width) as isize - x as isize) as i8;
+            directions[[y, x, 1]] = ((parent / width) as isize - y as isize) as i8;
+        }
+        directions
+    }
+
+    fn rust_batch_paths(
+        &self,
+        starts: &[Coords2D],
+        goals: &[Coords2D],
+        algorithm: Algorithm,
+        options: &SearchOptions,
+        limits: &SearchLimits,
+    ) -> Vec<RustPath> {
+        starts
+            .par_iter()
+            .zip(goals.par_iter())
+            .map(|(&start, &goal)| {
+                self.rust_limited_find_path(start, goal, algorithm, options, limits)
+                    .unwrap_or_else(|_| RustPath::not_found(None))
+            })
+            .collect()
     }
 }
 
 /// The name of the class can be changed here, e.g. 'name=PointCollection' and will then be available through my_library.PointCollection instead
-#[pyclass(name = "PointCollection")]
+#[pyclass(name = "PointCollection", module = "my_library")]
 pub struct PointCollection {
     #[pyo3(get, set)]
     points: Vec<RustPoint2>,
@@ -133,11 +1647,11 @@ impl PointCollection {
     }
 
     #[allow(dead_code)]
-    fn closest_point(&self, other: &RustPoint2) -> RustPoint2 {
-        // TODO raise error when list of points is empty
-        assert!(!self.points.is_empty());
+    fn closest_point(&self, other: &RustPoint2) -> PyResult<RustPoint2> {
         let mut iterable = self.points.clone().into_iter();
-        let mut closest = iterable.next().unwrap();
+        let mut closest = iterable
+            .next()
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("PointCollection is empty"))?;
         let mut distance_sq_closest = closest.distance_to_squared(other);
         for p in iterable {
             let p_distance_sq = p.distance_to_squared(other);
@@ -146,7 +1660,38 @@ impl PointCollection {
                 distance_sq_closest = p_distance_sq;
             }
         }
-        closest
+        Ok(closest)
+    }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        let points: Vec<Coords2D> = self.points.iter().map(|p| p.to_coords_2d()).collect();
+        PyBytes::new(py, &state::encode_points(&points))
+    }
+
+    fn __setstate__(&mut self, state: &PyBytes) -> PyResult<()> {
+        let points = state::decode_points(state.as_bytes())
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.points = points.iter().map(|&(x, y)| RustPoint2 { x, y }).collect();
+        Ok(())
+    }
+
+    fn __reduce__<'py>(&self, py: Python<'py>) -> (&'py PyType, (Vec<RustPoint2>,), &'py PyBytes) {
+        /// The points are restored from the compact state instead of one python object per point
+        (
+            py.get_type::<PointCollection>(),
+            (vec![],),
+            self.__getstate__(py),
+        )
+    }
+
+    fn __copy__(&self) -> Self {
+        PointCollection {
+            points: self.points.clone(),
+        }
+    }
+
+    fn __deepcopy__(&self, _memo: &PyDict) -> Self {
+        self.__copy__()
     }
 }
 
@@ -325,6 +1870,23 @@ fn rust_replace_int_with_char(my_array: ArrayView2<i64>) -> Array2<char> {
     c
 }
 
+fn rust_grid_to_map_chars<T: Copy + Into<i128>>(
+    grid: ArrayView2<T>,
+    walkable: &[i64],
+) -> Vec<char> {
+    /// Input: 2d ndarray of shape (height, width) with integers or bools
+    /// Output: row-major vec with '.' for values listed in walkable and 'O' for all other values
+    grid.iter()
+        .map(|&value| {
+            let value: i128 = value.into();
+            if walkable.iter().any(|&w| i128::from(w) == value) {
+                return '.';
+            }
+            'O'
+        })
+        .collect()
+}
+
 // fn rust_convert_numpy_array_to_1d_vec(my_array: ArrayView2<i64>) -> Vec<i64> {
 //     // let a = Array::from_iter(my_array.iter().cloned());
 //     /// Converts a 2d array to a 1 dimensional vec
@@ -347,10 +1909,71 @@ fn numpy_convert_to_1d_vec(_py: Python, x: PyReadonlyArray2<i64>) -> PyResult<Ve
     ))
 }
 
+#[pyfunction(flip_y = "false")]
+fn load_grid_text<'py>(py: Python<'py>, path: &str, flip_y: bool) -> PyResult<&'py PyArray2<u8>> {
+    /// Read a grid of digits with one line per row like AutomatonLE.txt into an uint8 array of shape (height, width)
+    /// The first line becomes row 0, with flip_y=True the last line does (SC2 counts y upwards)
+    /// Raises OSError if the file can not be read and ValueError with the line number of a malformed row
+    let contents = std::fs::read_to_string(path)?;
+    let (width, height, values) =
+        formats::parse_grid(&contents, flip_y).map_err(pyo3::exceptions::PyValueError::new_err)?;
+    Ok(Array2::from_shape_vec((height, width), values)
+        .unwrap()
+        .into_pyarray(py))
+}
+
+#[pyfunction(flip_y = "false")]
+fn save_grid_text(path: &str, array: &PyAny, flip_y: bool) -> PyResult<()> {
+    /// Write a 2d integer or bool array with values from 0 to 9 in the format read by load_grid_text
+    let (width, values) = extract_digit_grid(array)?;
+    let contents = formats::write_grid(width, &values, flip_y)
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    Ok(std::fs::write(path, contents)?)
+}
+
+fn extract_digit_grid(array: &PyAny) -> PyResult<(usize, Vec<u8>)> {
+    /// Width and row-major values of a 2d numpy array, values outside of 0 to 9 are rejected
+    if let Ok(grid) = array.extract::<PyReadonlyArray2<bool>>() {
+        let grid = grid.as_array();
+        return Ok((
+            grid.ncols(),
+            grid.iter().map(|&value| value as u8).collect(),
+        ));
+    }
+    macro_rules! try_dtypes {
+        ($($t:ty),*) => {
+            $(
+                if let Ok(grid) = array.extract::<PyReadonlyArray2<$t>>() {
+                    let grid = grid.as_array();
+                    let values = grid
+                        .iter()
+                        .map(|&value| {
+                            <u8 as std::convert::TryFrom<$t>>::try_from(value)
+                                .ok()
+                                .filter(|&digit| digit <= 9)
+                                .ok_or_else(|| {
+                                    pyo3::exceptions::PyValueError::new_err(format!(
+                                        "Only values from 0 to 9 can be written, got {}",
+                                        value
+                                    ))
+                                })
+                        })
+                        .collect::<PyResult<Vec<u8>>>()?;
+                    return Ok((grid.ncols(), values));
+                }
+            )*
+        };
+    }
+    try_dtypes!(u8, i8, u16, i16, u32, i32, u64, i64);
+    Err(pyo3::exceptions::PyTypeError::new_err(
+        "Expected a 2d numpy array of integers or bools",
+    ))
+}
+
 /// This module is a python module implemented in Rust.
 /// This function name has to be the same as the lib.name declared in Cargo.toml
 #[pymodule]
-fn my_library(_py: Python, m: &PyModule) -> PyResult<()> {
+fn my_library(py: Python, m: &PyModule) -> PyResult<()> {
     // Add all functions and classes (structs) here that need to be exported and callable via Python
 
     // Functions to be exported
@@ -381,6 +2004,8 @@ fn my_library(_py: Python, m: &PyModule) -> PyResult<()> {
 
     /// Pathfinding preparation with numpy
     m.add_wrapped(wrap_pyfunction!(numpy_convert_to_1d_vec))?;
+    m.add_wrapped(wrap_pyfunction!(load_grid_text))?;
+    m.add_wrapped(wrap_pyfunction!(save_grid_text))?;
 
     // m.add_wrapped(wrap_pyfunction!(mult_without_return))?;
     // m.add_wrapped(wrap_pyfunction!(mult_with_return))?;
@@ -390,13 +2015,30 @@ fn my_library(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<RustPoint2>()?;
     m.add_class::<RustPixelMap>()?;
     m.add_class::<PointCollection>()?;
+    m.add_class::<RustPath>()?;
+    m.add_class::<RustSearchStats>()?;
+    m.add_class::<RustPlanner>()?;
+    m.add_class::<RustMapAnalysis>()?;
+    m.add_class::<RustRegion>()?;
+    m.add_class::<RustChoke>()?;
+    m.add_class::<RustScenarioResult>()?;
+
+    // Exceptions to be exported
+    m.add("PathNotFoundError", py.get_type::<PathNotFoundError>())?;
 
     Ok(())
 }
 
 #[cfg(test)] // Only compiles when running tests
+#[allow(
+    clippy::assertions_on_constants,
+    clippy::clone_on_copy,
+    clippy::needless_borrow
+)]
 mod tests {
     use super::*;
+    use blitz_path::{a_star_path, jps_path};
+    use movingai::MovingAiMap;
     use ndarray::array;
     use numpy::ToPyArray;
     use test::Bencher;
@@ -672,6 +2314,65 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn bench_rust_grid_to_map_chars(b: &mut Bencher) {
+        b.iter(|| {
+            let a1 = array![[0u8, 1, 2], [2, 1, 0]];
+            let a2 = rust_grid_to_map_chars(a1.view(), &[1, 2]);
+            assert_eq!(a2, vec!['O', '.', '.', '.', '.', 'O']);
+            let a3 = array![[true, false], [false, true]];
+            let a4 = rust_grid_to_map_chars(a3.view(), &[1]);
+            assert_eq!(a4, vec!['.', 'O', 'O', '.']);
+        });
+    }
+
+    #[bench]
+    fn bench_pixel_map_from_numpy(b: &mut Bencher) {
+        b.iter(|| {
+            pyo3::Python::with_gil(|py| {
+                // 3 wide and 2 high, walkable cells are marked with 1
+                let a1 = array![[0i32, 1, 1], [1, 1, 0]];
+                let a2 = a1.to_pyarray(py);
+                let pixel_map = RustPixelMap::from_numpy(a2, vec![1]).unwrap();
+                assert_eq!(pixel_map.width(), 3);
+                assert_eq!(pixel_map.height(), 2);
+                assert!(pixel_map.map.is_traversable((2, 0)));
+                assert!(!pixel_map.map.is_traversable((2, 1)));
+
+                let a3 = array![[0.0f64, 1.0]].to_pyarray(py);
+                assert!(RustPixelMap::from_numpy(a3, vec![1]).is_err());
+            })
+        });
+    }
+
+    #[bench]
+    fn bench_pixel_map_non_square(b: &mut Bencher) {
+        b.iter(|| {
+            // 4 wide and 2 high in row-major order, the width comes first like in from_numpy
+            let my_map = array![['.', '.', '.', 'O'], ['O', '.', '.', '.']];
+            let pixel_map = RustPixelMap::new(4, 2, my_map.into_raw_vec()).unwrap();
+            assert_eq!((pixel_map.width(), pixel_map.height()), (4, 2));
+            assert!(!pixel_map.map.is_traversable((3, 0)));
+            assert!(!pixel_map.map.is_traversable((0, 1)));
+            assert!(pixel_map.map.is_traversable((3, 1)));
+            assert!(pixel_map.map.is_out_of_bound((0, 2)));
+            let options = SearchOptions::default();
+            let path = pixel_map
+                .rust_find_path((0, 0), (3, 1), Algorithm::AStar, &options)
+                .unwrap();
+            assert_eq!(path.waypoints.first(), Some(&(0, 0)));
+            assert_eq!(path.waypoints.last(), Some(&(3, 1)));
+            assert_eq!(
+                pixel_map.rust_find_path((0, 0), (1, 3), Algorithm::AStar, &options),
+                Err(PathError::OutOfBounds {
+                    point: (1, 3),
+                    width: 4,
+                    height: 2
+                })
+            );
+        });
+    }
+
     #[bench]
     fn bench_rust_moving_ai_map_astar(b: &mut Bencher) {
         b.iter(|| {
@@ -689,7 +2390,7 @@ mod tests {
             let my_moving_ai_map = MovingAiMap::new(String::from("test"), 5, 5, my_vec_1d);
             // println!("{:?}", my_vec_1d);
             if let Some(my_route) = a_star_path(&my_moving_ai_map, (1, 1), (3, 3)) {
-                /// TODO How to allow diagonal movement?
+                /// Only moves in 4 directions, RustPixelMap::find_path allows diagonal movement
                 let distance = my_route.distance();
                 let path = my_route.steps();
                 assert_eq!(distance, 4.0);
@@ -700,6 +2401,1371 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn bench_rust_find_path_options(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['O', 'O', 'O', 'O', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', '.', 'O', '.', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', 'O', 'O', 'O', 'O'],
+            ];
+            let pixel_map = RustPixelMap::new(5, 5, my_map.into_raw_vec()).unwrap();
+            let mut options = SearchOptions {
+                diagonal: false,
+                corner_cutting: CornerCutting::Never,
+                heuristic: Heuristic::Manhattan,
+                unit_radius: 0.0,
+            };
+            let path = pixel_map
+                .rust_find_path((1, 1), (3, 3), Algorithm::Auto, &options)
+                .unwrap();
+            assert_eq!(path.waypoints.len(), 5);
+            assert_eq!(path.waypoints.first(), Some(&(1, 1)));
+            assert_eq!(path.waypoints.last(), Some(&(3, 3)));
+            assert_eq!(path.cost, 4.0);
+
+            // Diagonal steps next to the wall in the center are not allowed without corner cutting
+            options.diagonal = true;
+            options.heuristic = Heuristic::Octile;
+            let path = pixel_map
+                .rust_find_path((1, 2), (2, 1), Algorithm::Auto, &options)
+                .unwrap();
+            assert_eq!(path.waypoints, vec![(1, 2), (1, 1), (2, 1)]);
+            options.corner_cutting = CornerCutting::Single;
+            let path = pixel_map
+                .rust_find_path((1, 2), (2, 1), Algorithm::Auto, &options)
+                .unwrap();
+            assert_eq!(path.waypoints, vec![(1, 2), (2, 1)]);
+
+            // JPS and A* return the same layout
+            options.corner_cutting = CornerCutting::Always;
+            options.heuristic = Heuristic::Euclidean;
+            let jps = pixel_map
+                .rust_find_path((1, 1), (3, 1), Algorithm::Jps, &options)
+                .unwrap();
+            let astar = pixel_map
+                .rust_find_path((1, 1), (3, 1), Algorithm::AStar, &options)
+                .unwrap();
+            assert_eq!(jps.waypoints, vec![(1, 1), (2, 1), (3, 1)]);
+            assert_eq!(jps.waypoints, astar.waypoints);
+            assert_eq!(jps.cost, astar.cost);
+            assert!(jps.reached_goal && astar.reached_goal);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_path(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['O', 'O', 'O', 'O', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', 'O', 'O', 'O', 'O'],
+            ];
+            let pixel_map = RustPixelMap::new(5, 5, my_map.into_raw_vec()).unwrap();
+            let path = pixel_map.rust_jps_path((1, 1), (3, 3), 0.0).unwrap();
+            assert_eq!(path.waypoints, vec![(1, 1), (2, 2), (3, 3)]);
+            assert_eq!(path.cost, 8.0f64.sqrt());
+            assert_eq!(path.nodes_expanded, Some(1));
+            assert_eq!(path.point_at_distance(0.0), Some((1.0, 1.0)));
+            assert_eq!(
+                path.point_at_distance(2.0f64.sqrt() / 2.0),
+                Some((1.5, 1.5))
+            );
+            assert_eq!(path.point_at_distance(100.0), Some((3.0, 3.0)));
+
+            // Start and goal are the same
+            let path = pixel_map.rust_astar_path((2, 2), (2, 2)).unwrap();
+            assert_eq!(path.waypoints, vec![(2, 2)]);
+            assert!(path.reached_goal);
+
+            let path = RustPath::not_found(None);
+            assert!(path.waypoints.is_empty());
+            assert_eq!(path.point_at_distance(1.0), None);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_path_errors(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['.', '.', 'O', '.'],
+                ['.', '.', 'O', '.'],
+                ['O', 'O', 'O', '.'],
+            ];
+            let pixel_map = RustPixelMap::new(4, 3, my_map.into_raw_vec()).unwrap();
+            let options = SearchOptions::default();
+            let out_of_bounds = PathError::OutOfBounds {
+                point: (4, 0),
+                width: 4,
+                height: 3,
+            };
+            assert_eq!(
+                pixel_map.rust_jps_path((0, 0), (4, 0), 0.0),
+                Err(out_of_bounds)
+            );
+            assert_eq!(
+                pixel_map.rust_astar_path((4, 0), (0, 0)),
+                Err(out_of_bounds)
+            );
+            assert_eq!(
+                pixel_map.rust_find_path((0, 0), (2, 0), Algorithm::Auto, &options),
+                Err(PathError::Blocked((2, 0)))
+            );
+            let not_found = PathError::NotFound {
+                start: (0, 0),
+                goal: (3, 0),
+            };
+            assert_eq!(pixel_map.rust_jps_path((0, 0), (3, 0), 0.0), Err(not_found));
+            assert_eq!(pixel_map.rust_astar_path((0, 0), (3, 0)), Err(not_found));
+            let path = pixel_map.rust_jps_path((0, 0), (1, 1), 0.0).unwrap();
+            assert_eq!(path.waypoints, vec![(0, 0), (1, 1)]);
+            assert_eq!(
+                pixel_map.rust_find_path((0, 0), (3, 0), Algorithm::AStar, &options),
+                Err(not_found)
+            );
+
+            // Only unreachable goals turn into None, out of bounds points always raise
+            assert!(matches!(path_or_none(Err(not_found), false), Ok(None)));
+            assert!(path_or_none(Err(not_found), true).is_err());
+            assert!(path_or_none(Err(out_of_bounds), false).is_err());
+
+            assert!(PointCollection::new(vec![])
+                .closest_point(&RustPoint2::new(0, 0))
+                .is_err());
+        });
+    }
+
+    #[bench]
+    fn bench_rust_replanning(b: &mut Bencher) {
+        b.iter(|| {
+            let mut pixel_map = RustPixelMap::new(5, 5, vec!['.'; 25]).unwrap();
+            let options = SearchOptions {
+                diagonal: false,
+                corner_cutting: CornerCutting::Never,
+                heuristic: Heuristic::Manhattan,
+                unit_radius: 0.0,
+            };
+            let mut planner = DStarLite::new(5, 5, (0, 2), (4, 2), options);
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            assert_eq!(path.cost, 4.0);
+            assert_eq!(path.waypoints.first(), Some(&(0, 2)));
+            assert_eq!(path.waypoints.last(), Some(&(4, 2)));
+
+            // Nothing changed, nothing to repair
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            assert_eq!(path.cost, 4.0);
+            assert_eq!(path.nodes_expanded, Some(0));
+
+            pixel_map.rust_set_walkable((2, 2), (1, 1), false).unwrap();
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            assert_eq!(path.cost, 6.0);
+            assert!(!path.waypoints.contains(&(2, 2)));
+
+            planner.set_start((1, 2));
+            pixel_map.rust_set_walkable((2, 0), (1, 5), false).unwrap();
+            assert_eq!(
+                pixel_map.rust_plan(&mut planner),
+                Err(PathError::NotFound {
+                    start: (1, 2),
+                    goal: (4, 2)
+                })
+            );
+            pixel_map.rust_set_walkable((2, 0), (1, 5), true).unwrap();
+            assert_eq!(pixel_map.rust_plan(&mut planner).unwrap().cost, 3.0);
+            assert!(pixel_map.rust_set_walkable((4, 4), (2, 1), false).is_err());
+
+            // Repaired paths cost the same as searching from scratch
+            let mut pixel_map = RustPixelMap::new(10, 10, vec!['.'; 100]).unwrap();
+            let options = SearchOptions::default();
+            let mut planner = DStarLite::new(10, 10, (0, 0), (9, 9), options);
+            let mut seed: usize = 7;
+            for step in 0..30 {
+                seed = (seed * 1_103_515_245 + 12_345) % 2_147_483_648;
+                let tile = (seed % 10, (seed / 10) % 10);
+                if tile != planner.start() && tile != planner.goal() {
+                    pixel_map
+                        .rust_set_walkable(tile, (1, 1), step % 3 == 0)
+                        .unwrap();
+                }
+                if step % 5 == 0 {
+                    planner.set_start((step / 5, 0));
+                }
+                let start = planner.start();
+                let repaired = pixel_map.rust_plan(&mut planner).map(|path| path.cost);
+                let fresh = pixel_map
+                    .rust_find_path(start, (9, 9), Algorithm::AStar, &options)
+                    .map(|path| path.cost);
+                match (repaired, fresh) {
+                    (Ok(repaired), Ok(fresh)) => assert!((repaired - fresh).abs() < 1e-9),
+                    (repaired, fresh) => assert_eq!(repaired, fresh),
+                }
+            }
+
+            // Only the logged rectangles are repaired, a change far away from the search costs nothing
+            let mut pixel_map = RustPixelMap::new(50, 50, vec!['.'; 2500]).unwrap();
+            let mut planner = DStarLite::new(50, 50, (0, 0), (9, 0), options);
+            assert_eq!(pixel_map.rust_plan(&mut planner).unwrap().cost, 9.0);
+            pixel_map
+                .rust_set_walkable((40, 40), (2, 2), false)
+                .unwrap();
+            pixel_map.set_cost(RustPoint2::new(45, 30), 3.0).unwrap();
+            assert_eq!(pixel_map.changes.version(), 2);
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            assert_eq!(path.nodes_expanded, Some(0));
+            pixel_map.set_cost(RustPoint2::new(5, 0), 3.0).unwrap();
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            assert!((path.cost - (7.0 + 2.0 * std::f64::consts::SQRT_2)).abs() < 1e-9);
+
+            // A planner that missed dropped entries of the log searches from scratch
+            for _ in 0..=ChangeLog::CAPACITY {
+                pixel_map.set_cost(RustPoint2::new(45, 30), 2.0).unwrap();
+            }
+            assert!(pixel_map.changes.since(0).is_none());
+            pixel_map.rust_set_walkable((4, 0), (1, 2), false).unwrap();
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            let fresh = pixel_map
+                .rust_find_path((0, 0), (9, 0), Algorithm::AStar, &options)
+                .unwrap();
+            assert!((path.cost - fresh.cost).abs() < 1e-9);
+            // Replaced weights count as a change of the whole map
+            pixel_map.clear_costs();
+            let path = pixel_map.rust_plan(&mut planner).unwrap();
+            let fresh = pixel_map
+                .rust_find_path((0, 0), (9, 0), Algorithm::AStar, &options)
+                .unwrap();
+            assert!((path.cost - fresh.cost).abs() < 1e-9);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_clearance(b: &mut Bencher) {
+        b.iter(|| {
+            // A wall across the map with a narrow gap at x=2 and a wide gap at x=6..=8
+            let mut tiles = vec!['.'; 11 * 7];
+            for x in (0..11).filter(|&x| x != 2 && !(6..=8).contains(&x)) {
+                tiles[3 * 11 + x] = 'O';
+            }
+            let pixel_map = RustPixelMap::new(11, 7, tiles).unwrap();
+            assert_eq!(pixel_map.rust_clearance()[3 * 11], 0.0);
+            assert_eq!(pixel_map.rust_clearance()[3 * 11 + 2], 0.5);
+            assert_eq!(pixel_map.rust_clearance()[3 * 11 + 7], 1.5);
+            // Next to the border of the map
+            assert_eq!(pixel_map.rust_clearance()[0], 0.5);
+            let open_map = RustPixelMap::new(5, 5, vec!['.'; 25]).unwrap();
+            assert_eq!(open_map.rust_clearance()[2 * 5 + 2], 2.5);
+
+            // Searches without a unit radius never run the transform
+            let mut lazy_map = RustPixelMap::new(11, 7, vec!['.'; 77]).unwrap();
+            lazy_map.rust_set_walkable((3, 3), (2, 1), false).unwrap();
+            lazy_map
+                .rust_find_path((0, 0), (10, 6), Algorithm::AStar, &SearchOptions::default())
+                .unwrap();
+            assert!(!lazy_map.clearance.is_computed());
+            // New walls lower the clearance around them, the same as running the transform again
+            assert_eq!(lazy_map.rust_clearance()[3 * 11 + 8], 2.5);
+            let mut seed: usize = 3;
+            for step in 0..40 {
+                seed = (seed * 1_103_515_245 + 12_345) % 2_147_483_648;
+                let corner = (seed % 10, (seed / 10) % 6);
+                let size = (1 + (seed / 60) % 2, 1 + (seed / 120) % 2);
+                lazy_map
+                    .rust_set_walkable(corner, size, step % 4 == 0)
+                    .unwrap();
+                assert_eq!(
+                    lazy_map.rust_clearance(),
+                    &clearance::clearance(&lazy_map.map)[..]
+                );
+            }
+
+            let mut options = SearchOptions::default();
+            let path = pixel_map
+                .rust_find_path((2, 1), (2, 5), Algorithm::Auto, &options)
+                .unwrap();
+            assert!(path.waypoints.contains(&(2, 3)));
+
+            // A big unit has to take the wide gap
+            options.unit_radius = 1.0;
+            let path = pixel_map
+                .rust_find_path((2, 1), (2, 5), Algorithm::Auto, &options)
+                .unwrap();
+            assert!(path.waypoints.contains(&(7, 3)));
+            assert!(!path.waypoints.contains(&(2, 3)));
+            let distances = pixel_map.rust_distance_field(&[(2, 1)], &options);
+            assert_eq!(distances[[3, 2]], f64::INFINITY);
+            // jps_path and astar_path take the same unit_radius
+            for path in [
+                pixel_map.rust_jps_path((2, 1), (2, 5), 1.0).unwrap(),
+                pixel_map
+                    .rust_limited_astar_path((2, 1), (2, 5), 1.0, &SearchLimits::default())
+                    .unwrap(),
+            ]
+            .iter()
+            {
+                assert!(path.waypoints.contains(&(7, 3)));
+                assert!(!path.waypoints.contains(&(2, 3)));
+            }
+
+            // Too big to stand at the start
+            options.unit_radius = 2.0;
+            assert_eq!(
+                pixel_map.rust_find_path((2, 1), (2, 5), Algorithm::Auto, &options),
+                Err(PathError::Blocked((2, 1)))
+            );
+            assert_eq!(
+                pixel_map.rust_jps_path((2, 1), (2, 5), 2.0),
+                Err(PathError::Blocked((2, 1)))
+            );
+        });
+    }
+
+    #[bench]
+    fn bench_rust_smooth_path(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['.', '.', '.', '.', '.', '.'],
+                ['.', '.', '.', '.', '.', '.'],
+                ['.', '.', '.', 'O', 'O', '.'],
+                ['.', '.', '.', 'O', 'O', '.'],
+            ];
+            let pixel_map = RustPixelMap::new(6, 4, my_map.into_raw_vec()).unwrap();
+            let map = pixel_map.view(0.0);
+            assert!(line_of_sight::line_of_sight(&map, (0.0, 0.0), (5.0, 1.0)));
+            assert!(!line_of_sight::line_of_sight(&map, (0.0, 3.0), (5.0, 3.0)));
+            // Passing exactly through the corner of a wall is blocked
+            assert!(!line_of_sight::line_of_sight(&map, (2.0, 3.0), (4.0, 1.0)));
+            assert!(!line_of_sight::line_of_sight(&map, (0.0, 0.0), (6.0, 0.0)));
+
+            let mut visited = vec![];
+            line_of_sight::traverse((0.0, 0.0), (2.0, 1.0), |tile| {
+                visited.push(tile);
+                true
+            });
+            assert_eq!(visited, vec![(0, 0), (1, 0), (1, 1), (2, 1)]);
+
+            let options = SearchOptions::default();
+            let path = pixel_map
+                .rust_find_path((0, 3), (5, 3), Algorithm::Auto, &options)
+                .unwrap();
+            let smoothed = line_of_sight::smooth(&map, &path.waypoints);
+            assert_eq!(smoothed.first(), Some(&(0.0, 3.0)));
+            assert_eq!(smoothed.last(), Some(&(5.0, 3.0)));
+            assert!(smoothed.len() < path.waypoints.len());
+            for pair in smoothed.windows(2) {
+                assert!(line_of_sight::line_of_sight(&map, pair[0], pair[1]));
+            }
+
+            // Nothing to smooth on a straight line
+            let smoothed = line_of_sight::smooth(&map, &[(0, 0), (1, 0), (2, 0)]);
+            assert_eq!(smoothed, vec![(0.0, 0.0), (2.0, 0.0)]);
+            assert_eq!(line_of_sight::smooth(&map, &[(1, 1)]), vec![(1.0, 1.0)]);
+
+            // Corner cutting steps go around the corner instead of touching the wall
+            let my_map = array![['.', 'O', '.'], ['.', '.', '.'], ['O', '.', '.']];
+            let pixel_map = RustPixelMap::new(3, 3, my_map.into_raw_vec()).unwrap();
+            let map = pixel_map.view(0.0);
+            let options = SearchOptions {
+                corner_cutting: CornerCutting::Single,
+                ..SearchOptions::default()
+            };
+            let path = pixel_map
+                .rust_find_path((0, 0), (2, 0), Algorithm::AStar, &options)
+                .unwrap();
+            assert_eq!(path.waypoints, vec![(0, 0), (1, 1), (2, 0)]);
+            let smoothed = line_of_sight::smooth(&map, &path.waypoints);
+            assert_eq!(
+                smoothed,
+                vec![(0.0, 0.0), (0.0, 1.0), (2.0, 1.0), (2.0, 0.0)]
+            );
+            // Jump points of JPS are not neighbors and their diagonals cut corners as well
+            let jps = pixel_map.rust_jps_path((0, 1), (1, 2), 0.0).unwrap();
+            let smoothed = line_of_sight::smooth(&map, &jps.waypoints);
+            assert_eq!(smoothed.last(), Some(&(1.0, 2.0)));
+            for path in [path, jps].iter() {
+                let smoothed = line_of_sight::smooth(&map, &path.waypoints);
+                for pair in smoothed.windows(2) {
+                    assert!(line_of_sight::line_of_sight(&map, pair[0], pair[1]));
+                }
+            }
+            // Between two walls there is no way around
+            let pixel_map = RustPixelMap::new(2, 2, vec!['.', 'O', 'O', '.']).unwrap();
+            let smoothed = line_of_sight::smooth(&pixel_map.view(0.0), &[(0, 0), (1, 1)]);
+            assert_eq!(smoothed, vec![(0.0, 0.0), (1.0, 1.0)]);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_raycast(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['.', '.', '.', '.', '.'],
+                ['.', '.', '.', 'O', '.'],
+                ['.', '.', '.', '.', '.'],
+            ];
+            let pixel_map = RustPixelMap::new(5, 3, my_map.into_raw_vec()).unwrap();
+            let raycast = |origin, direction, max_distance| {
+                line_of_sight::raycast(&pixel_map.map, origin, direction, max_distance)
+            };
+            // The wall at (3, 1) starts at x = 2.5
+            assert_eq!(raycast((0.0, 1.0), (1.0, 0.0), 10.0), Some((2.5, 1.0)));
+            assert_eq!(raycast((0.0, 1.0), (2.0, 0.0), 2.0), None);
+            // The map border counts as wall
+            assert_eq!(raycast((1.0, 1.0), (0.0, -1.0), 10.0), Some((1.0, -0.5)));
+            assert_eq!(raycast((4.0, 0.0), (1.0, 0.0), 10.0), Some((4.5, 0.0)));
+            // Starting inside of a wall
+            assert_eq!(raycast((3.0, 1.0), (1.0, 0.0), 10.0), Some((3.0, 1.0)));
+            let hit = raycast((0.0, 0.0), (3.0, 1.0), 10.0).unwrap();
+            assert!((hit.0 - 2.5).abs() < 1e-9 && (hit.1 - 2.5 / 3.0).abs() < 1e-9);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_components(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['.', '.', 'O', '.', '.'],
+                ['.', 'O', 'O', 'O', '.'],
+                ['O', 'O', '.', 'O', '.'],
+            ];
+            let mut pixel_map = RustPixelMap::new(5, 3, my_map.into_raw_vec()).unwrap();
+            let components = &pixel_map.components;
+            assert_eq!(components.count(), 3);
+            assert_eq!(
+                components.labels(),
+                &[0, 0, -1, 1, 1, 0, -1, -1, -1, 1, -1, -1, 2, -1, 1]
+            );
+            assert!(components.is_reachable((0, 0), (1, 0)));
+            assert!(!components.is_reachable((0, 0), (3, 0)));
+            assert!(!components.is_reachable((0, 0), (2, 0)));
+            assert_eq!(components.component_of((2, 2)), Some(2));
+            assert_eq!(components.component_of((2, 1)), None);
+
+            // Opening a gap merges areas, closing it splits them again
+            pixel_map.rust_set_walkable((2, 0), (1, 1), true).unwrap();
+            assert_eq!(pixel_map.components.count(), 2);
+            assert!(pixel_map.components.is_reachable((0, 0), (4, 2)));
+            pixel_map.rust_set_walkable((2, 0), (1, 1), false).unwrap();
+            assert_eq!(pixel_map.components.count(), 3);
+            assert!(!pixel_map.components.is_reachable((0, 0), (4, 2)));
+            pixel_map.rust_set_walkable((2, 1), (1, 1), true).unwrap();
+            assert!(pixel_map.components.is_reachable((2, 2), (2, 1)));
+            assert!(!pixel_map.components.is_reachable((2, 2), (0, 0)));
+
+            // Only searches that cut corners can pass between two diagonal walls
+            let pixel_map = RustPixelMap::new(2, 2, vec!['.', 'O', 'O', '.']).unwrap();
+            assert!(!pixel_map.components.is_reachable((0, 0), (1, 1)));
+            let mut options = SearchOptions::default();
+            for &(corner_cutting, found) in [
+                (CornerCutting::Never, false),
+                (CornerCutting::Single, false),
+                (CornerCutting::Always, true),
+            ]
+            .iter()
+            {
+                options.corner_cutting = corner_cutting;
+                let path = pixel_map.rust_find_path((0, 0), (1, 1), Algorithm::AStar, &options);
+                assert_eq!(path.is_ok(), found);
+            }
+
+            // Blocking and opening only relabel the touched areas, the result matches labelling from scratch
+            let mut pixel_map = RustPixelMap::new(12, 12, vec!['.'; 144]).unwrap();
+            pixel_map.rust_set_walkable((0, 6), (12, 1), false).unwrap();
+            let lower = pixel_map.components.component_of((0, 11));
+            let mut seed: usize = 11;
+            for step in 0..60 {
+                seed = (seed * 1_103_515_245 + 12_345) % 2_147_483_648;
+                let corner = (seed % 11, (seed / 11) % 5);
+                let size = (1 + (seed / 55) % 2, 1 + (seed / 110) % 2);
+                pixel_map
+                    .rust_set_walkable(corner, size, step % 3 == 0)
+                    .unwrap();
+                assert_eq!(pixel_map.components.component_of((0, 11)), lower);
+                let fresh = Components::new(&pixel_map.map);
+                assert_eq!(pixel_map.components.count(), fresh.count());
+                // Same areas, possibly under other labels
+                let mut labels = HashMap::new();
+                for (label, fresh_label) in pixel_map
+                    .components
+                    .labels()
+                    .into_iter()
+                    .zip(fresh.labels())
+                {
+                    assert_eq!(*labels.entry(fresh_label).or_insert(label), label);
+                }
+                let mut used: Vec<i32> = labels.values().cloned().collect();
+                used.sort_unstable();
+                used.dedup();
+                assert_eq!(used.len(), labels.len());
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_rust_map_analysis(b: &mut Bencher) {
+        b.iter(|| {
+            // Two rooms joined by a gap of one cell
+            let my_map = array![
+                ['.', '.', '.', 'O', '.', '.', '.'],
+                ['.', '.', '.', '.', '.', '.', '.'],
+                ['.', '.', '.', 'O', '.', '.', '.'],
+            ];
+            let pixel_map = RustPixelMap::new(7, 3, my_map.into_raw_vec()).unwrap();
+            let analysis = RustMapAnalysis::new(&pixel_map.map, pixel_map.rust_clearance(), 0.7);
+            assert_eq!(
+                analysis.labels(),
+                &[0, 0, 0, -1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0, -1, 1, 1, 1]
+            );
+            let regions = analysis.regions();
+            assert_eq!(regions.len(), 2);
+            assert_eq!((regions[0].area, regions[1].area), (10, 9));
+            assert_eq!((regions[0].center, regions[1].center), ((1, 1), (5, 1)));
+            assert_eq!(regions[0].neighbors, vec![1]);
+            let choke = analysis.choke_between(1, 0).unwrap();
+            assert_eq!(choke.points, vec![(3, 1), (4, 1)]);
+            assert_eq!((choke.start, choke.end), ((3, 1), (3, 1)));
+            assert!((choke.width - 1.0).abs() < 1e-9);
+            assert_eq!(analysis.region_at((5, 2)).unwrap().unwrap().id, 1);
+            assert_eq!(analysis.region_at((3, 0)), Ok(None));
+            assert!(analysis.region_at((7, 0)).is_err());
+
+            // Without splitting everything is one region
+            let analysis = RustMapAnalysis::new(&pixel_map.map, pixel_map.rust_clearance(), 0.0);
+            assert_eq!(analysis.regions().len(), 1);
+            assert!(analysis.chokes().is_empty());
+        });
+    }
+
+    #[bench]
+    fn bench_rust_hpa_path(b: &mut Bencher) {
+        // A wall with two gaps splits the map into a left and a right half
+        let (width, height) = (40, 30);
+        let mut tiles = vec!['.'; width * height];
+        for y in 0..height {
+            if y != 3 && y != 25 {
+                tiles[y * width + 19] = 'O';
+            }
+        }
+        let mut pixel_map = RustPixelMap::new(width, height, tiles).unwrap();
+        let options = SearchOptions::default();
+        let (start, goal) = ((2, 24), (37, 26));
+        b.iter(|| {
+            pixel_map.hierarchy = None;
+            let exact = pixel_map
+                .rust_find_path(start, goal, Algorithm::AStar, &options)
+                .unwrap();
+            let path = pixel_map
+                .rust_hpa_path(start, goal, 8, &options, true)
+                .unwrap();
+            assert_eq!(path.waypoints.first(), Some(&start));
+            assert_eq!(path.waypoints.last(), Some(&goal));
+            assert!(path.cost >= exact.cost - 1e-9 && path.cost < exact.cost * 1.2);
+            let coarse = pixel_map
+                .rust_hpa_path(start, goal, 8, &options, false)
+                .unwrap();
+            assert!(coarse.waypoints.len() < path.waypoints.len());
+            assert!((coarse.cost - path.cost).abs() < 1e-9);
+
+            // Closing the lower gap only rebuilds the clusters around it, the path takes the upper gap
+            pixel_map
+                .rust_set_walkable((19, 25), (1, 1), false)
+                .unwrap();
+            let detour = pixel_map
+                .rust_hpa_path(start, goal, 8, &options, true)
+                .unwrap();
+            assert!(detour.waypoints.contains(&(19, 3)));
+            pixel_map.rust_set_walkable((19, 3), (1, 1), false).unwrap();
+            assert_eq!(
+                pixel_map.rust_hpa_path(start, goal, 8, &options, true),
+                Err(PathError::NotFound { start, goal })
+            );
+            pixel_map.rust_set_walkable((19, 3), (1, 1), true).unwrap();
+            pixel_map.rust_set_walkable((19, 25), (1, 1), true).unwrap();
+            let reopened = pixel_map
+                .rust_hpa_path(start, goal, 8, &options, true)
+                .unwrap();
+            assert!((reopened.cost - path.cost).abs() < 1e-9);
+
+            // Same cluster
+            let short = pixel_map
+                .rust_hpa_path((1, 1), (3, 2), 8, &options, true)
+                .unwrap();
+            assert_eq!(short.waypoints.len(), 3);
+            assert!((short.cost - (1.0 + std::f64::consts::SQRT_2)).abs() < 1e-9);
+
+            // Weights around the goal are paid on the entered cells, the same way as by the refined path
+            pixel_map.clear_costs();
+            for x in 32..40 {
+                for y in 20..30 {
+                    let weight = 1.0 + ((x + 2 * y) % 4) as f64;
+                    pixel_map
+                        .cost_grid_mut()
+                        .set_weight((x, y), weight)
+                        .unwrap();
+                }
+            }
+            pixel_map.hierarchy = None;
+            let weighted = pixel_map
+                .rust_hpa_path(start, goal, 8, &options, true)
+                .unwrap();
+            let costs = pixel_map.costs.as_ref().unwrap();
+            let walked: f64 = weighted
+                .waypoints
+                .windows(2)
+                .map(|pair| {
+                    let diagonal = pair[0].0 != pair[1].0 && pair[0].1 != pair[1].1;
+                    let step = if diagonal {
+                        std::f64::consts::SQRT_2
+                    } else {
+                        1.0
+                    };
+                    step * costs.weight(pair[1].1 * width + pair[1].0)
+                })
+                .sum();
+            assert!((weighted.cost - walked).abs() < 1e-9);
+            let exact = pixel_map
+                .rust_find_path(start, goal, Algorithm::AStar, &options)
+                .unwrap();
+            assert!(weighted.cost >= exact.cost - 1e-9 && weighted.cost < exact.cost * 1.2);
+            pixel_map.clear_costs();
+            pixel_map.hierarchy = None;
+        });
+    }
+
+    #[bench]
+    fn bench_rust_scenarios(b: &mut Bencher) {
+        b.iter(|| {
+            let map = "type octile\nheight 3\nwidth 4\nmap\n....\n.@T.\nG..S\n";
+            let (width, height, tiles) = formats::parse_map(map).unwrap();
+            assert_eq!((width, height), (4, 3));
+            assert_eq!(
+                tiles.iter().collect::<String>(),
+                "....\
+                 .OO.\
+                 ...."
+            );
+            assert!(formats::parse_map("type octile\nheight 2\nwidth 4\nmap\n....\n").is_err());
+            assert!(formats::parse_map("type octile\nheight 1\nwidth 4\nmap\n...\n").is_err());
+
+            let scen = "version 1\n0\ttest.map\t4\t3\t0\t0\t3\t0\t3\n\
+                        1\ttest.map\t4\t3\t0\t1\t3\t1\t5\n";
+            let scenarios = formats::parse_scenarios(scen).unwrap();
+            assert_eq!(scenarios.len(), 2);
+            assert_eq!(scenarios[1].start_pos, (0, 1));
+            assert!(formats::parse_scenarios("0\ttest.map\t4\t3\n").is_err());
+
+            let pixel_map = RustPixelMap::new(width, height, tiles).unwrap();
+            let octile = SearchOptions {
+                diagonal: true,
+                corner_cutting: CornerCutting::Never,
+                heuristic: Heuristic::Octile,
+                ..SearchOptions::default()
+            };
+            let run = |pixel_map: &RustPixelMap, scenarios| {
+                benchmark::run(
+                    scenarios,
+                    |start, goal| pixel_map.rust_jps_path(start, goal, 0.0),
+                    |start, goal| pixel_map.rust_astar_path(start, goal),
+                    |start, goal| pixel_map.rust_find_path(start, goal, Algorithm::AStar, &octile),
+                )
+            };
+            let results = run(&pixel_map, &scenarios);
+            assert_eq!(results[0].astar_length, Some(3.0));
+            assert_eq!(results[0].optimal_length, 3.0);
+            assert_eq!(results[1].astar_length, Some(5.0));
+            // JPS cuts the corners of the walls, so only the octile column is comparable to the optimal length
+            assert!(results[1].jps_length.unwrap() < 5.0);
+
+            // Diagonal move around a wall, with the optimal length rounded to 8 decimals like in MovingAI files
+            let map = "type octile\nheight 4\nwidth 5\nmap\n.....\n.@@..\n..@..\n.....\n";
+            let (width, height, tiles) = formats::parse_map(map).unwrap();
+            let scen = "version 1\n0\tdiagonal.map\t5\t4\t0\t0\t4\t3\t6.41421356\n\
+                        0\tdiagonal.map\t5\t4\t1\t3\t4\t0\t5.41421356\n";
+            let diagonal_scenarios = formats::parse_scenarios(scen).unwrap();
+            let diagonal_map = RustPixelMap::new(width, height, tiles).unwrap();
+            for result in run(&pixel_map, &scenarios)
+                .iter()
+                .chain(run(&diagonal_map, &diagonal_scenarios).iter())
+            {
+                let length = result.octile_length.unwrap();
+                assert!(
+                    (length - result.optimal_length).abs() < 1e-6,
+                    "{:?}",
+                    result
+                );
+            }
+            // astar_path moves in 4 directions, so paths with diagonal moves come out longer
+            let diagonal = run(&diagonal_map, &diagonal_scenarios);
+            assert_eq!(diagonal[0].astar_length, Some(7.0));
+            assert!(diagonal[0].astar_length.unwrap() > diagonal[0].optimal_length);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_grid_text(b: &mut Bencher) {
+        let contents = std::fs::read_to_string("AutomatonLE.txt").unwrap();
+        b.iter(|| {
+            let (width, height, values) = formats::parse_grid(&contents, false).unwrap();
+            assert_eq!((width, height), (184, 192));
+            assert_eq!(
+                formats::write_grid(width, &values, false).unwrap(),
+                contents.trim_end()
+            );
+
+            let (_, _, flipped) = formats::parse_grid("01\n23\n\n", true).unwrap();
+            assert_eq!(flipped, vec![2, 3, 0, 1]);
+            assert_eq!(formats::write_grid(2, &flipped, true).unwrap(), "01\n23");
+            assert_eq!(
+                formats::parse_grid("01\n2\n", false),
+                Err("Line 2: expected 2 digits, got 1".to_string())
+            );
+            assert_eq!(
+                formats::parse_grid("01\n2x\n", false),
+                Err("Line 2, column 2: expected a digit, got 'x'".to_string())
+            );
+            assert!(formats::write_grid(1, &[10], false).is_err());
+        });
+    }
+
+    #[bench]
+    fn bench_rust_render_png(b: &mut Bencher) {
+        let my_map = array![['.', '.', '.'], ['O', 'O', '.'], ['.', '.', '.']];
+        let pixel_map = RustPixelMap::new(3, 3, my_map.into_raw_vec()).unwrap();
+        b.iter(|| {
+            let mut canvas = Canvas::new(&pixel_map.map);
+            let field = pixel_map.rust_distance_field(&[(0, 0)], &SearchOptions::default());
+            canvas
+                .draw(&Overlay::Heatmap(field.into_raw_vec()), [0, 0, 0])
+                .unwrap();
+            // Walls stay dark, the closest and the farthest cells are blue and red
+            assert_eq!(canvas.pixels()[3], [40, 40, 40]);
+            assert!(canvas.pixels()[0][2] > canvas.pixels()[0][0]);
+            assert!(canvas.pixels()[6][0] > canvas.pixels()[6][2]);
+
+            canvas
+                .draw(&Overlay::Path(vec![(0, 0), (2, 0), (2, 2)]), [255, 0, 0])
+                .unwrap();
+            let red: Vec<usize> = (0..9)
+                .filter(|&i| canvas.pixels()[i] == [255, 0, 0])
+                .collect();
+            assert_eq!(red, vec![0, 1, 2, 5, 8]);
+            assert!(canvas
+                .draw(&Overlay::Labels(vec![0; 4]), [0, 0, 0])
+                .is_err());
+
+            let png = canvas.to_png(2);
+            assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+            // Width and height of the image in the header
+            assert_eq!(&png[16..24], &[0, 0, 0, 6, 0, 0, 0, 6]);
+            assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xaeB`\x82");
+        });
+    }
+
+    #[bench]
+    fn bench_rust_pickle_state(b: &mut Bencher) {
+        let my_map = array![['.', '.', '.'], ['O', 'O', '.'], ['.', '.', '.']];
+        let mut pixel_map = RustPixelMap::new(3, 3, my_map.into_raw_vec()).unwrap();
+        pixel_map.costs = Some(CostGrid::new(3, 3));
+        pixel_map
+            .costs
+            .as_mut()
+            .unwrap()
+            .set_weight((2, 1), 3.0)
+            .unwrap();
+        b.iter(|| {
+            let bytes = pixel_map.rust_state().encode();
+            // Header, size, 2 bytes of walkability, the weight flag and 9 weights
+            assert_eq!(bytes.len(), 4 + 16 + 2 + 1 + 72);
+            let restored =
+                RustPixelMap::rust_from_state(MapState::decode(&bytes).unwrap()).unwrap();
+            assert_eq!(restored.rust_state(), pixel_map.rust_state());
+            assert_eq!(restored.rust_clearance(), pixel_map.rust_clearance());
+            assert_eq!(restored.components.count(), 1);
+
+            assert!(MapState::decode(&bytes[..bytes.len() - 1]).is_err());
+            assert!(MapState::decode(&[bytes.as_slice(), &[0]].concat()).is_err());
+            assert!(MapState::decode(b"RPP1").is_err());
+
+            let points = vec![(0, 0), (2, 1), (usize::MAX, 7)];
+            let encoded = state::encode_points(&points);
+            assert_eq!(state::decode_points(&encoded).unwrap(), points);
+            assert!(state::decode_points(&encoded[..20]).is_err());
+        });
+    }
+
+    #[bench]
+    fn bench_rust_bit_grid(b: &mut Bencher) {
+        let tiles: Vec<char> = (0..35)
+            .map(|index| {
+                if index % 7 == 3 || index % 11 == 0 {
+                    'T'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let chars = MovingAiMap::new(String::from("test"), 5, 7, tiles.clone());
+        let bits = BitGrid::from_tiles(7, 5, &tiles);
+        let pixel_map = RustPixelMap::new(7, 5, tiles).unwrap();
+        let file = std::env::temp_dir().join("my_library_bit_grid.rpm");
+        std::fs::write(&file, pixel_map.rust_state().encode()).unwrap();
+        b.iter(|| {
+            // Same answers as the MovingAiMap it replaces
+            assert_eq!(bits.free_states(), chars.free_states());
+            for tile in chars.coords() {
+                assert_eq!(bits.is_traversable(tile), chars.is_traversable(tile));
+                assert_eq!(
+                    *bits.get(tile),
+                    if chars.is_traversable(tile) { '.' } else { 'O' }
+                );
+                if tile.0 > 0 && tile.1 > 0 {
+                    assert_eq!(bits.neighbors(tile), chars.neighbors(tile));
+                }
+            }
+            assert_eq!(
+                grid::tiles(&bits).collect::<Vec<_>>(),
+                chars.coords().collect::<Vec<_>>()
+            );
+            // Building a CoordsIter would need a char for every tile
+            assert!(std::panic::catch_unwind(|| bits.coords()).is_err());
+
+            let mut mapped = RustPixelMap::rust_from_mapped(
+                MappedFile::open_path(file.to_str().unwrap()).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(mapped.map.is_mapped(), cfg!(unix));
+            assert_eq!(mapped.map.bytes(), pixel_map.map.bytes());
+            assert_eq!(mapped.rust_clearance(), pixel_map.rust_clearance());
+            // Changes copy the tiles and leave the file as it is
+            mapped.rust_set_walkable((3, 0), (1, 1), true).unwrap();
+            assert!(!mapped.map.is_mapped() && mapped.map.is_traversable((3, 0)));
+            assert_eq!(
+                std::fs::read(&file).unwrap(),
+                pixel_map.rust_state().encode()
+            );
+
+            assert!(BitGrid::from_bytes(7, 5, vec![0; 4]).is_err());
+            assert!(RustPixelMap::new(7, 5, vec!['.'; 34]).is_err());
+            // Same walkable tiles in map files and in tiles passed to the map, as in MovingAiMap
+            let map = "type octile\nheight 1\nwidth 5\nmap\n.GSWT\n";
+            let (_, _, parsed) = formats::parse_map(map).unwrap();
+            let tiles = ['.', 'G', 'S', 'W', 'T'];
+            let direct = BitGrid::from_tiles(5, 1, &tiles);
+            let walkable: Vec<bool> = parsed.iter().map(|&tile| tile == '.').collect();
+            assert_eq!(walkable, vec![true, true, true, true, false]);
+            assert_eq!(direct.bytes(), BitGrid::new(5, 1, &walkable).bytes());
+            let moving_ai = MovingAiMap::new(String::new(), 1, 5, tiles.to_vec());
+            let traversable: Vec<bool> = (0..5).map(|x| moving_ai.is_traversable((x, 0))).collect();
+            assert_eq!(traversable, walkable);
+            let mut newer = pixel_map.rust_state().encode();
+            newer[3] = b'2';
+            assert!(MapLayout::read(&newer).unwrap_err().contains("version '2'"));
+        });
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    /// AutomatonLE and 40 random pairs of walkable tiles away from x = 0 and y = 0, where blitz-path underflows
+    fn automaton_pairs() -> (BitGrid, Vec<(Coords2D, Coords2D)>) {
+        let contents = std::fs::read_to_string("AutomatonLE.txt").unwrap();
+        let (width, height, values) = formats::parse_grid(&contents, false).unwrap();
+        let walkable: Vec<bool> = values.iter().map(|&value| value == 1).collect();
+        let map = BitGrid::new(width, height, &walkable);
+        let free: Vec<Coords2D> = grid::tiles(&map)
+            .filter(|&(x, y)| x > 0 && y > 0 && map.is_traversable((x, y)))
+            .collect();
+        let mut seed = 12345usize;
+        let mut pick = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            free[(seed >> 33) % free.len()]
+        };
+        let pairs = (0..40).map(|_| (pick(), pick())).collect();
+        (map, pairs)
+    }
+
+    #[bench]
+    fn bench_rust_search_arena(b: &mut Bencher) {
+        let (map, pairs) = automaton_pairs();
+        let width = map.width();
+
+        // Same paths as blitz-path, which starts from scratch for every query
+        for &(start, goal) in pairs.iter() {
+            let ours = jps::jps(&map, start, goal);
+            let blitz = RustPath::from_route(jps_path(&map, start, goal), start);
+            assert_eq!(ours.waypoints, blitz.waypoints);
+            assert_eq!(ours.cost, blitz.cost);
+        }
+        let options = SearchOptions::default();
+        let limits = SearchLimits::default();
+        let distances = pathfinding::dijkstra(&map, None, &[pairs[0].0], &options).distances;
+        for &(_, goal) in pairs.iter() {
+            let path = pathfinding::astar(&map, None, pairs[0].0, goal, &options, &limits);
+            assert!((path.cost - distances[goal.1 * width + goal.0]).abs() < 1e-9);
+            let both = pathfinding::bidirectional_astar(&map, None, pairs[0].0, goal, &options);
+            assert!((both.cost - path.cost).abs() < 1e-9);
+        }
+
+        b.iter(|| {
+            for &(start, goal) in pairs.iter() {
+                assert!(jps::jps(&map, start, goal).reached_goal);
+                assert!(
+                    pathfinding::astar(&map, None, start, goal, &options, &limits).reached_goal
+                );
+            }
+            // A search inside of a search gets its own arena
+            arena::with_arena(|outer| {
+                let path =
+                    pathfinding::astar(&map, None, pairs[1].0, pairs[1].1, &options, &limits);
+                let (start, goal) = pairs[1];
+                let inner =
+                    pathfinding::astar_in(outer, &map, None, start, goal, &options, &limits);
+                assert_eq!(path, inner);
+            });
+        });
+    }
+
+    #[bench]
+    fn bench_blitz_jps_path(b: &mut Bencher) {
+        // Baseline for bench_rust_search_arena, blitz-path allocates its open list and nodes for every query
+        let (map, pairs) = automaton_pairs();
+        b.iter(|| {
+            for &(start, goal) in pairs.iter() {
+                assert!(jps_path(&map, start, goal).is_some());
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_rust_jps_path(b: &mut Bencher) {
+        // Only the JPS queries of bench_rust_search_arena, compare with bench_blitz_jps_path
+        let (map, pairs) = automaton_pairs();
+        b.iter(|| {
+            for &(start, goal) in pairs.iter() {
+                assert!(jps::jps(&map, start, goal).reached_goal);
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_rust_limited_search(b: &mut Bencher) {
+        // The goals at (5, 0) and (5, 1) are walled in
+        let rows = ["....O.O", "....O.O", "....OOO", ".......", "......."];
+        let pixel_map = RustPixelMap::new(7, 5, rows.concat().chars().collect()).unwrap();
+        let options = SearchOptions::default();
+        let limits = SearchLimits::default();
+        let limited = SearchLimits {
+            max_expansions: Some(3),
+            max_distance: None,
+        };
+
+        // Bidirectional search finds paths of the same cost and gives up from the goal side
+        for &(start, goal) in [((0, 0), (6, 4)), ((3, 0), (0, 4)), ((0, 2), (6, 3))].iter() {
+            let astar = pathfinding::astar(&pixel_map.map, None, start, goal, &options, &limits);
+            let both =
+                pathfinding::bidirectional_astar(&pixel_map.map, None, start, goal, &options);
+            assert!(both.reached_goal);
+            assert!((both.cost - astar.cost).abs() < 1e-9);
+            assert_eq!(
+                (both.waypoints[0], *both.waypoints.last().unwrap()),
+                (start, goal)
+            );
+        }
+        // Steps are paid on the entered tile in both directions
+        let weights = (0..35).map(|index| 1.0 + (index * 7 % 5) as f64).collect();
+        let costs = CostGrid::from_weights(7, 5, weights).unwrap();
+        let tiles: Vec<Coords2D> = grid::tiles(&pixel_map.map)
+            .filter(|&tile| pixel_map.map.is_traversable(tile))
+            .collect();
+        for &start in tiles.iter() {
+            for &goal in tiles.iter() {
+                let map = &pixel_map.map;
+                let astar = pathfinding::astar(map, Some(&costs), start, goal, &options, &limits);
+                let both =
+                    pathfinding::bidirectional_astar(map, Some(&costs), start, goal, &options);
+                assert_eq!(both.reached_goal, astar.reached_goal);
+                assert!(!astar.reached_goal || (both.cost - astar.cost).abs() < 1e-9);
+            }
+        }
+        let blocked =
+            pathfinding::bidirectional_astar(&pixel_map.map, None, (0, 0), (5, 0), &options);
+        assert!(blocked.waypoints.is_empty() && blocked.nodes_expanded.unwrap() <= 3);
+        assert_eq!(
+            pixel_map.rust_find_path((0, 0), (5, 0), Algorithm::Bidirectional, &options),
+            Err(PathError::NotFound {
+                start: (0, 0),
+                goal: (5, 0)
+            })
+        );
+
+        // Unreachable and blocked goals lead to the closest explored tile
+        let unreachable = pixel_map
+            .rust_limited_astar_path(
+                (0, 4),
+                (5, 0),
+                0.0,
+                &SearchLimits {
+                    max_expansions: Some(100),
+                    max_distance: None,
+                },
+            )
+            .unwrap();
+        assert!(!unreachable.reached_goal);
+        assert_eq!(unreachable.waypoints.last(), Some(&(3, 0)));
+        assert_eq!(unreachable.cost, unreachable.waypoints.len() as f64 - 1.0);
+        let walled = pixel_map
+            .rust_limited_find_path((0, 0), (4, 0), Algorithm::Auto, &options, &limited)
+            .unwrap();
+        assert_eq!(walled.nodes_expanded, Some(3));
+        let near = pixel_map
+            .rust_limited_find_path(
+                (0, 0),
+                (6, 4),
+                Algorithm::Auto,
+                &options,
+                &SearchLimits {
+                    max_expansions: None,
+                    max_distance: Some(2.0),
+                },
+            )
+            .unwrap();
+        assert!(!near.reached_goal && near.cost <= 2.0);
+        assert_eq!(near.waypoints.last(), Some(&(2, 0)));
+        // Limits that are not hit do not change the path
+        let path = pixel_map.rust_find_path((0, 0), (6, 4), Algorithm::AStar, &options);
+        let generous = SearchLimits {
+            max_expansions: Some(1000),
+            max_distance: Some(100.0),
+        };
+        assert_eq!(
+            path,
+            pixel_map.rust_limited_find_path((0, 0), (6, 4), Algorithm::Auto, &options, &generous)
+        );
+
+        b.iter(|| pathfinding::bidirectional_astar(&pixel_map.map, None, (0, 0), (6, 4), &options));
+    }
+
+    #[bench]
+    fn bench_rust_search_stats(b: &mut Bencher) {
+        let mut tiles = vec!['.'; 64];
+        tiles[3 * 8 + 3] = 'O';
+        let mut pixel_map = RustPixelMap::new(8, 8, tiles).unwrap();
+        let options = SearchOptions::default();
+        let find = |pixel_map: &RustPixelMap, algorithm| {
+            pixel_map
+                .rust_find_path((0, 0), (7, 6), algorithm, &options)
+                .unwrap()
+        };
+        assert_eq!(find(&pixel_map, Algorithm::AStar).stats, None);
+
+        pixel_map.search_stats = StatsMode::Stats;
+        let path = find(&pixel_map, Algorithm::AStar);
+        let stats = path.stats.clone().unwrap();
+        assert_eq!(Some(stats.nodes_expanded), path.nodes_expanded);
+        assert!(stats.open_list_peak <= stats.nodes_generated);
+        assert!(stats.nodes_generated >= stats.nodes_expanded);
+        assert_eq!(stats.trace, None);
+        // Counters start over for every query
+        let again = find(&pixel_map, Algorithm::AStar).stats.unwrap();
+        assert_eq!(again.nodes_generated, stats.nodes_generated);
+        let jps = find(&pixel_map, Algorithm::Jps);
+        assert_eq!(
+            jps.stats.unwrap().nodes_expanded,
+            jps.nodes_expanded.unwrap()
+        );
+        let both = find(&pixel_map, Algorithm::Bidirectional);
+        assert_eq!(
+            both.stats.unwrap().nodes_expanded,
+            both.nodes_expanded.unwrap()
+        );
+        let hpa = pixel_map
+            .rust_hpa_path((0, 0), (7, 6), 4, &options, true)
+            .unwrap();
+        assert_eq!(
+            hpa.stats.unwrap().nodes_expanded,
+            hpa.nodes_expanded.unwrap()
+        );
+
+        pixel_map.search_stats = StatsMode::Trace;
+        let trace = find(&pixel_map, Algorithm::AStar)
+            .stats
+            .unwrap()
+            .trace
+            .unwrap();
+        assert_eq!(trace.len(), stats.nodes_expanded);
+        assert_eq!((trace[0], *trace.last().unwrap()), ((0, 0), (7, 6)));
+        let limits = SearchLimits::default();
+        let paths =
+            pixel_map.rust_batch_paths(&[(0, 0)], &[(7, 6)], Algorithm::AStar, &options, &limits);
+        assert_eq!(
+            paths[0].stats.as_ref().unwrap().trace.as_ref(),
+            Some(&trace)
+        );
+
+        b.iter(|| find(&pixel_map, Algorithm::AStar));
+    }
+
+    #[bench]
+    fn bench_rust_async_snapshot(b: &mut Bencher) {
+        let mut pixel_map = RustPixelMap::new(6, 6, vec!['.'; 36]).unwrap();
+        let options = SearchOptions::default();
+        let snapshot = pixel_map.rust_snapshot();
+        assert!(Arc::ptr_eq(&snapshot, &pixel_map.rust_snapshot()));
+
+        // Pending queries keep searching the map as it was when they were started
+        pixel_map.rust_set_walkable((0, 3), (6, 1), false).unwrap();
+        let search = |map: &RustPixelMap| {
+            map.rust_find_path((0, 0), (5, 5), Algorithm::AStar, &options)
+                .is_ok()
+        };
+        assert!(search(&snapshot) && !search(&pixel_map));
+        let changed = pixel_map.rust_snapshot();
+        assert!(!Arc::ptr_eq(&snapshot, &changed) && !search(&changed));
+        pixel_map.cost_grid_mut();
+        assert!(!Arc::ptr_eq(&changed, &pixel_map.rust_snapshot()));
+
+        b.iter(|| pixel_map.rust_snapshot());
+    }
+
+    #[bench]
+    fn bench_rust_custom_astar(b: &mut Bencher) {
+        let pixel_map = RustPixelMap::new(5, 5, vec!['.'; 25]).unwrap();
+        let options = SearchOptions::default();
+        let limits = SearchLimits::default();
+        let custom = |step_cost: &dyn Fn(Coords2D, Coords2D, f64) -> Result<f64, String>| {
+            arena::with_arena(|arena| {
+                pathfinding::astar_by(
+                    arena,
+                    &pixel_map.map,
+                    None,
+                    (0, 2),
+                    (4, 2),
+                    &options,
+                    &limits,
+                    step_cost,
+                    |tile| Ok(options.heuristic.distance(tile, (4, 2))),
+                )
+            })
+        };
+
+        // Passing the costs through is the plain search
+        let plain = pathfinding::astar(&pixel_map.map, None, (0, 2), (4, 2), &options, &limits);
+        assert_eq!(custom(&|_, _, cost| Ok(cost)), Ok(plain.clone()));
+        // Forbidden steps are walked around
+        let avoid =
+            custom(&|_, to, cost| Ok(if to == (2, 2) { f64::INFINITY } else { cost })).unwrap();
+        assert!(avoid.reached_goal && !avoid.waypoints.contains(&(2, 2)));
+        assert!(avoid.cost > plain.cost);
+        // The first error stops the search
+        let failed = custom(&|from, _, _| Err(format!("{:?}", from)));
+        assert_eq!(failed, Err(String::from("(0, 2)")));
+
+        // Partial paths end at the cheaper of two tiles with the same estimate, 0.1 + 0.3 - 0.1 is not 0.3
+        let corridor = RustPixelMap::new(4, 1, vec!['.', '.', '.', 'O']).unwrap();
+        let limited = SearchLimits {
+            max_expansions: Some(3),
+            max_distance: None,
+        };
+        let partial: Result<_, std::convert::Infallible> = arena::with_arena(|arena| {
+            pathfinding::astar_by(
+                arena,
+                &corridor.map,
+                None,
+                (1, 0),
+                (3, 0),
+                &options,
+                &limited,
+                |_, to, _| Ok(if to == (0, 0) { 0.1 } else { 0.2 }),
+                |tile| Ok(if tile == (1, 0) { 1.0 } else { 0.3 }),
+            )
+        });
+        let partial = partial.unwrap();
+        assert!(!partial.reached_goal);
+        assert_eq!(partial.waypoints, vec![(1, 0), (0, 0)]);
+
+        b.iter(|| custom(&|_, _, cost| Ok(cost)));
+    }
+
+    #[bench]
+    fn bench_rust_batch_paths(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['O', 'O', 'O', 'O', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', '.', 'O', '.', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', 'O', 'O', 'O', 'O'],
+            ];
+            let pixel_map = RustPixelMap::new(5, 5, my_map.into_raw_vec()).unwrap();
+            let options = SearchOptions::default();
+            let starts = vec![(1, 1), (3, 3), (1, 1), (2, 1)];
+            let goals = vec![(3, 3), (1, 1), (2, 2), (2, 1)];
+            let limits = SearchLimits::default();
+            let paths =
+                pixel_map.rust_batch_paths(&starts, &goals, Algorithm::Auto, &options, &limits);
+            assert_eq!(paths.len(), 4);
+            for ((path, &start), &goal) in paths.iter().zip(starts.iter()).zip(goals.iter()) {
+                let single = pixel_map
+                    .rust_find_path(start, goal, Algorithm::Auto, &options)
+                    .unwrap_or_else(|_| RustPath::not_found(None));
+                assert_eq!(path.waypoints, single.waypoints);
+            }
+            assert_eq!(paths[0].waypoints.first(), Some(&(1, 1)));
+            assert_eq!(paths[1].waypoints.first(), Some(&(3, 3)));
+            // Goal is a wall
+            assert!(!paths[2].reached_goal);
+        });
+    }
+
+    #[bench]
+    fn bench_rust_distance_and_flow_field(b: &mut Bencher) {
+        b.iter(|| {
+            let my_map = array![
+                ['O', 'O', 'O', 'O', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', '.', 'O', '.', 'O'],
+                ['O', '.', '.', '.', 'O'],
+                ['O', 'O', 'O', 'O', '.'],
+            ];
+            let pixel_map = RustPixelMap::new(5, 5, my_map.into_raw_vec()).unwrap();
+            let options = SearchOptions::default();
+            let distances = pixel_map.rust_distance_field(&[(1, 1)], &options);
+            assert_eq!(distances.dim(), (5, 5));
+            assert_eq!(distances[[1, 1]], 0.0);
+            assert_eq!(distances[[1, 3]], 2.0);
+            assert_eq!(distances[[3, 3]], 4.0);
+            assert_eq!(distances[[2, 2]], f64::INFINITY);
+            // Walkable but cut off by the walls
+            assert_eq!(distances[[4, 4]], f64::INFINITY);
+
+            // Multiple sources: distance to the closest one
+            let distances = pixel_map.rust_distance_field(&[(1, 1), (3, 3)], &options);
+            assert_eq!(distances[[1, 3]], 2.0);
+            assert_eq!(distances[[3, 2]], 1.0);
+
+            let directions = pixel_map.rust_flow_field((1, 1), &options);
+            assert_eq!(directions.dim(), (5, 5, 2));
+            // At the goal and on walls there is nowhere to go
+            assert_eq!((directions[[1, 1, 0]], directions[[1, 1, 1]]), (0, 0));
+            assert_eq!((directions[[0, 0, 0]], directions[[0, 0, 1]]), (0, 0));
+            // Following the directions leads to the goal
+            let mut position = (3, 3);
+            for _ in 0..4 {
+                let (x, y) = position;
+                position = (
+                    (x as i8 + directions[[y, x, 0]]) as usize,
+                    (y as i8 + directions[[y, x, 1]]) as usize,
+                );
+            }
+            assert_eq!(position, (1, 1));
+
+            // On a weighted map the directions follow the cheapest path, not the shortest one
+            let mut pixel_map = RustPixelMap::new(6, 6, vec!['.'; 36]).unwrap();
+            for (x, y) in (0..6).flat_map(|x| (0..6).map(move |y| (x, y))) {
+                let weight = 1.0 + ((x * 7 + y * 13) % 5) as f64;
+                pixel_map.set_cost(RustPoint2::new(x, y), weight).unwrap();
+            }
+            let directions = pixel_map.rust_flow_field((5, 2), &options);
+            let costs = pixel_map.costs.clone().unwrap();
+            for start in (0..6).flat_map(|x| (0..6).map(move |y| (x, y))) {
+                let mut position = start;
+                let mut cost = 0.0;
+                while position != (5, 2) {
+                    let (x, y) = position;
+                    let step = (directions[[y, x, 0]], directions[[y, x, 1]]);
+                    assert_ne!(step, (0, 0));
+                    position = ((x as i8 + step.0) as usize, (y as i8 + step.1) as usize);
+                    let length = if step.0 != 0 && step.1 != 0 {
+                        std::f64::consts::SQRT_2
+                    } else {
+                        1.0
+                    };
+                    cost += length * costs.weight(position.1 * 6 + position.0);
+                }
+                let path = pixel_map
+                    .rust_find_path(start, (5, 2), Algorithm::AStar, &options)
+                    .unwrap();
+                assert!((cost - path.cost).abs() < 1e-9);
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_rust_cost_grid(b: &mut Bencher) {
+        b.iter(|| {
+            let mut pixel_map = RustPixelMap::new(5, 3, vec!['.'; 15]).unwrap();
+            let options = SearchOptions {
+                diagonal: false,
+                corner_cutting: CornerCutting::Never,
+                heuristic: Heuristic::Manhattan,
+                unit_radius: 0.0,
+            };
+            let path = pixel_map
+                .rust_find_path((0, 1), (4, 1), Algorithm::Auto, &options)
+                .unwrap();
+            assert_eq!(path.cost, 4.0);
+
+            // An expensive cell in the middle is avoided instead of walked through
+            pixel_map.set_cost(RustPoint2::new(2, 1), 10.0).unwrap();
+            let path = pixel_map
+                .rust_find_path((0, 1), (4, 1), Algorithm::Auto, &options)
+                .unwrap();
+            assert_eq!(path.cost, 6.0);
+            assert!(!path.waypoints.contains(&(2, 1)));
+            // Walking from the expensive cell to the source only pays for the cells it enters
+            let distances = pixel_map.rust_distance_field(&[(0, 1)], &options);
+            assert_eq!(distances[[1, 2]], 2.0);
+            // With uneven weights the distances are the costs of the searched paths to the source
+            pixel_map.set_cost(RustPoint2::new(1, 0), 3.0).unwrap();
+            pixel_map.set_cost(RustPoint2::new(3, 2), 2.5).unwrap();
+            for options in [options, SearchOptions::default()].iter() {
+                let distances = pixel_map.rust_distance_field(&[(0, 1)], options);
+                for (x, y) in (0..5).flat_map(|x| (0..3).map(move |y| (x, y))) {
+                    let path = pixel_map
+                        .rust_find_path((x, y), (0, 1), Algorithm::AStar, options)
+                        .unwrap();
+                    assert!((distances[[y, x]] - path.cost).abs() < 1e-9);
+                }
+            }
+            assert!(pixel_map.set_cost(RustPoint2::new(2, 1), 0.5).is_err());
+            assert!(pixel_map.set_cost(RustPoint2::new(5, 1), 2.0).is_err());
+            pixel_map.clear_costs();
+            let path = pixel_map
+                .rust_find_path((0, 1), (4, 1), Algorithm::Auto, &options)
+                .unwrap();
+            assert_eq!(path.cost, 4.0);
+
+            let mut costs = CostGrid::new(5, 3);
+            costs.add_circle((2.0, 1.0), 2.0, 4.0, Falloff::Linear);
+            assert_eq!(costs.weight(7), 5.0);
+            assert_eq!(costs.weight(6), 3.0);
+            assert_eq!(costs.weight(5), 1.0);
+            costs.add_circle((2.0, 1.0), 2.0, 4.0, Falloff::Quadratic);
+            assert_eq!(costs.weight(6), 4.0);
+            costs.add_circle((2.0, 1.0), 2.0, -4.0, Falloff::Quadratic);
+            costs.add_circle((2.0, 1.0), 2.0, -4.0, Falloff::Linear);
+            assert_eq!(costs, CostGrid::new(5, 3));
+            assert!(CostGrid::from_weights(5, 3, vec![1.0; 14]).is_err());
+            assert!(CostGrid::from_weights(5, 3, vec![f64::NAN; 15]).is_err());
+        });
+    }
+
+    #[bench]
+    fn bench_rust_heuristics(b: &mut Bencher) {
+        b.iter(|| {
+            let (a, c) = ((0, 0), (3, 4));
+            assert_eq!(Heuristic::Manhattan.distance(a, c), 7.0);
+            assert_eq!(Heuristic::Euclidean.distance(a, c), 5.0);
+            assert_eq!(Heuristic::Chebyshev.distance(a, c), 4.0);
+            assert_eq!(Heuristic::Octile.distance(a, c), 1.0 + 3.0 * 2.0f64.sqrt());
+            // Manhattan is only admissible without diagonal steps
+            assert!(parse_search_options("auto", false, "never", Some("manhattan"), 0.0).is_ok());
+            assert!(parse_search_options("auto", true, "never", Some("manhattan"), 0.0).is_err());
+        });
+    }
+
     #[bench]
     fn bench_rust_moving_ai_map_jps(b: &mut Bencher) {
         b.iter(|| {
@@ -0,0 +1,192 @@
+// Straight lines over the grid
+// Tile (x, y) covers the square from (x - 0.5, y - 0.5) to (x + 0.5, y + 0.5), so integer points are tile centers
+
+use std::cmp::Ordering;
+
+use movingai::{Coords2D, Map2D};
+
+const EPSILON: f64 = 1e-9;
+
+/// Walking direction along one axis, the fraction of the segment needed to cross one tile
+/// and the fraction at which the first tile border is crossed
+fn axis(origin: f64, delta: f64, cell: isize) -> (isize, f64, f64) {
+    if delta > 0.0 {
+        (1, 1.0 / delta, (cell as f64 + 1.0 - origin) / delta)
+    } else if delta < 0.0 {
+        (-1, -1.0 / delta, (origin - cell as f64) / -delta)
+    } else {
+        (0, f64::INFINITY, f64::INFINITY)
+    }
+}
+
+/// Calls 'visit' for every tile the segment touches, in order from 'from' to 'to' (supercover)
+/// A segment that passes exactly through a corner touches both tiles next to the corner
+/// Stops early and returns false as soon as 'visit' returns false or the segment leaves the map on the negative side
+pub fn traverse<F: FnMut(Coords2D) -> bool>(
+    from: (f64, f64),
+    to: (f64, f64),
+    mut visit: F,
+) -> bool {
+    let mut visit_cell =
+        |(x, y): (isize, isize)| x >= 0 && y >= 0 && visit((x as usize, y as usize));
+    // Shifted so that tile i covers [i, i + 1)
+    let (x0, y0) = (from.0 + 0.5, from.1 + 0.5);
+    let (x1, y1) = (to.0 + 0.5, to.1 + 0.5);
+    let mut cell = (x0.floor() as isize, y0.floor() as isize);
+    let end = (x1.floor() as isize, y1.floor() as isize);
+    let (step_x, t_delta_x, mut t_max_x) = axis(x0, x1 - x0, cell.0);
+    let (step_y, t_delta_y, mut t_max_y) = axis(y0, y1 - y0, cell.1);
+    if !visit_cell(cell) {
+        return false;
+    }
+    // Guards against rounding errors stepping past the end tile
+    let max_steps = (end.0 - cell.0).abs() + (end.1 - cell.1).abs();
+    for _ in 0..max_steps {
+        if cell == end {
+            break;
+        }
+        if (t_max_x - t_max_y).abs() < EPSILON {
+            if !visit_cell((cell.0 + step_x, cell.1)) || !visit_cell((cell.0, cell.1 + step_y)) {
+                return false;
+            }
+            cell = (cell.0 + step_x, cell.1 + step_y);
+            t_max_x += t_delta_x;
+            t_max_y += t_delta_y;
+        } else if t_max_x < t_max_y {
+            cell.0 += step_x;
+            t_max_x += t_delta_x;
+        } else {
+            cell.1 += step_y;
+            t_max_y += t_delta_y;
+        }
+        if !visit_cell(cell) {
+            return false;
+        }
+    }
+    true
+}
+
+/// True if every tile the segment touches is walkable
+pub fn line_of_sight<U, T: Map2D<U>>(map: &T, from: (f64, f64), to: (f64, f64)) -> bool {
+    traverse(from, to, |tile| map.is_traversable(tile))
+}
+
+fn to_point(tile: Coords2D) -> (f64, f64) {
+    (tile.0 as f64, tile.1 as f64)
+}
+
+/// Next coordinate from 'from' towards 'to'
+fn step_towards(from: usize, to: usize) -> usize {
+    match from.cmp(&to) {
+        Ordering::Less => from + 1,
+        Ordering::Greater => from - 1,
+        Ordering::Equal => from,
+    }
+}
+
+/// Every tile between the waypoints, diagonal steps past the corner of a wall go around it through the walkable
+/// orthogonal tile, e.g. for paths of jps_path or with corner cutting, so neighboring tiles can see each other
+/// A diagonal step between two walls has no way around and is kept as it is
+fn unit_steps<U, T: Map2D<U>>(map: &T, waypoints: &[Coords2D]) -> Vec<Coords2D> {
+    let mut steps = Vec::with_capacity(waypoints.len());
+    let mut current = match waypoints.first() {
+        Some(&first) => first,
+        None => return steps,
+    };
+    steps.push(current);
+    for &target in &waypoints[1..] {
+        while current != target {
+            let next = (
+                step_towards(current.0, target.0),
+                step_towards(current.1, target.1),
+            );
+            let diagonal = next.0 != current.0 && next.1 != current.1;
+            if diagonal && !line_of_sight(map, to_point(current), to_point(next)) {
+                let corners = [(next.0, current.1), (current.0, next.1)];
+                if let Some(&corner) = corners.iter().find(|&&tile| map.is_traversable(tile)) {
+                    steps.push(corner);
+                }
+            }
+            steps.push(next);
+            current = next;
+        }
+    }
+    steps
+}
+
+/// String pulling: drops every waypoint that the previous kept waypoint can see past
+/// The result starts and ends with the same points as 'waypoints', segments between the points are unobstructed
+/// Diagonal steps past a wall corner get the tile next to the corner as an extra point, only diagonal steps
+/// between two walls, which corner_cutting="always" takes, touch walls
+pub fn smooth<U, T: Map2D<U>>(map: &T, waypoints: &[Coords2D]) -> Vec<(f64, f64)> {
+    let waypoints = unit_steps(map, waypoints);
+    let mut result = Vec::with_capacity(waypoints.len());
+    let (first, last) = match (waypoints.first(), waypoints.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return result,
+    };
+    result.push(to_point(first));
+    let mut anchor = to_point(first);
+    for pair in waypoints.windows(2).skip(1) {
+        let (previous, next) = (to_point(pair[0]), to_point(pair[1]));
+        if !line_of_sight(map, anchor, next) {
+            result.push(previous);
+            anchor = previous;
+        }
+    }
+    if waypoints.len() > 1 {
+        result.push(to_point(last));
+    }
+    result
+}
+
+/// Fractions of the ray at which it enters and leaves the axis aligned box, enter > exit if it misses the box
+fn slab(origin: (f64, f64), unit: (f64, f64), min: (f64, f64), max: (f64, f64)) -> (f64, f64) {
+    let axis = |origin: f64, unit: f64, min: f64, max: f64| {
+        if unit == 0.0 {
+            if origin < min || origin > max {
+                return (f64::INFINITY, f64::NEG_INFINITY);
+            }
+            return (f64::NEG_INFINITY, f64::INFINITY);
+        }
+        let (t1, t2) = ((min - origin) / unit, (max - origin) / unit);
+        (t1.min(t2), t1.max(t2))
+    };
+    let (enter_x, exit_x) = axis(origin.0, unit.0, min.0, max.0);
+    let (enter_y, exit_y) = axis(origin.1, unit.1, min.1, max.1);
+    (enter_x.max(enter_y), exit_x.min(exit_y))
+}
+
+/// Point where a ray first touches a wall or leaves the map, None if it travels 'max_distance' without doing so
+/// 'direction' does not need to be normalized but can not be (0, 0), 'max_distance' has to be at least 0
+pub fn raycast<U, T: Map2D<U>>(
+    map: &T,
+    origin: (f64, f64),
+    direction: (f64, f64),
+    max_distance: f64,
+) -> Option<(f64, f64)> {
+    let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+    let unit = (direction.0 / length, direction.1 / length);
+    let at = |t: f64| (origin.0 + unit.0 * t, origin.1 + unit.1 * t);
+    let map_max = (map.width() as f64 - 0.5, map.height() as f64 - 0.5);
+    let (_, map_exit) = slab(origin, unit, (-0.5, -0.5), map_max);
+    let end = max_distance.min(map_exit.max(0.0));
+
+    let mut hit = None;
+    traverse(origin, at(end), |tile| {
+        if map.is_traversable(tile) {
+            return true;
+        }
+        hit = Some(tile);
+        false
+    });
+    if let Some((x, y)) = hit {
+        let (x, y) = (x as f64, y as f64);
+        let (enter, _) = slab(origin, unit, (x - 0.5, y - 0.5), (x + 0.5, y + 0.5));
+        return Some(at(enter.max(0.0).min(end)));
+    }
+    if map_exit <= max_distance {
+        return Some(at(end));
+    }
+    None
+}
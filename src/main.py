@@ -1,4 +1,9 @@
+import os
+import tempfile
 import time
+import asyncio
+import copy
+import pickle
 from math import factorial
 import numpy as np
 import my_library
@@ -28,12 +33,9 @@ def pathfinding_tests():
             break
 
     data = random_bot_object.game_info.pathing_grid.data_numpy
-    data.ravel()
-    # data.flat
-    # data.flatten()
-
-    width = 184
-    height = 192
+    # Width and height are taken from the shape of the array, 1 marks a pathable cell
+    pixel_map = my_library.RustPixelMap.from_numpy(data)
+    print(f"Loaded map of size {pixel_map.width}x{pixel_map.height}")
 
     # point_test = my_library.Point3d(3, 4, 5)
     # print(point_test)
@@ -115,6 +117,229 @@ def basic_tests():
     assert isinstance(result, list)
     assert result == expected
 
+    # Build a pixel map directly from a numpy array of shape (height, width)
+    pixel_map = my_library.RustPixelMap.from_numpy(my_array)
+    assert (pixel_map.width, pixel_map.height) == (3, 3)
+    pixel_map = my_library.RustPixelMap.from_numpy(my_array.astype(bool))
+    assert (pixel_map.width, pixel_map.height) == (3, 3)
+
+    # Paths are returned from start to goal
+    path = pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    assert list(path) == [(1, 1), (2, 1), (2, 2)], list(path)
+    assert path.reached_goal and path.cost == 2
+    assert path[-1] == (2, 2)
+    assert path.to_numpy().shape == (3, 2)
+
+    # Walls and disconnected goals raise PathNotFoundError, points outside of the map raise ValueError
+    try:
+        pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(0, 0))
+        assert False, "Function above should throw error on a blocked goal"
+    except my_library.PathNotFoundError:
+        pass
+    assert pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(0, 0), raise_not_found=False) is None
+    # With a search limit the path leads as close to an unreachable goal as possible instead
+    path = pixel_map.find_path(my_library.RustPoint2(2, 2), my_library.RustPoint2(0, 0), max_expansions=50)
+    assert not path.reached_goal and list(path)[-1] == (1, 1)
+    path = pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), algorithm="bidirectional")
+    assert path.reached_goal and path.cost == 2
+    # Search statistics to find slow queries, "trace" also records the expanded tiles for animations
+    pixel_map.search_stats = "trace"
+    path = pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    assert path.stats.nodes_expanded == path.nodes_expanded and path.stats.seconds >= 0
+    assert path.stats.trace.shape == (path.stats.nodes_expanded, 2)
+    pixel_map.search_stats = "off"
+    assert pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2)).stats is None
+    # Awaitable queries for asyncio bots, the search runs on a worker thread while the event loop continues
+    async def query():
+        return await pixel_map.path_async(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    assert asyncio.run(query()).cost == 2
+    # Cancelled queries stay cancelled, a result that arrives afterwards is dropped instead of set on the future
+    async def cancelled_queries():
+        errors = []
+        asyncio.get_running_loop().set_exception_handler(lambda loop, context: errors.append(context))
+        big_map = my_library.RustPixelMap(200, 200, ["."] * 40000)
+        for delay in [None, 0, 0.001]:
+            future = big_map.path_async(my_library.RustPoint2(0, 0), my_library.RustPoint2(199, 199))
+            if delay is not None:
+                await asyncio.sleep(delay)
+            future.cancel()
+            try:
+                await future
+                assert False, "Cancelled future should raise CancelledError"
+            except asyncio.CancelledError:
+                pass
+            assert future.cancelled()
+        # Time for the workers to hand in their results
+        await asyncio.sleep(0.2)
+        return errors
+    assert asyncio.run(cancelled_queries()) == []
+    # One-off rules as python callbacks, returning math.inf forbids a step
+    path = pixel_map.astar_path_custom(
+        my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), cost=lambda a, b, base_cost: base_cost * 2
+    )
+    assert path.cost == 4
+    try:
+        pixel_map.astar_path_custom(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), heuristic=lambda a, b: 1 / 0)
+        assert False, "Function above should raise the error of the callback"
+    except ZeroDivisionError:
+        pass
+    for estimate in [-1.0, float("nan")]:
+        try:
+            pixel_map.astar_path_custom(
+                my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), heuristic=lambda a, b: estimate
+            )
+            assert False, "Function above should throw error on a negative estimate"
+        except ValueError:
+            pass
+    try:
+        pixel_map.astar_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(5, 5))
+        assert False, "Function above should throw error on a point outside of the map"
+    except ValueError:
+        pass
+    try:
+        pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), heuristic="manhattan")
+        assert False, "Function above should throw error on manhattan with diagonal steps"
+    except ValueError:
+        pass
+
+    # Many queries at once, computed in parallel without holding the GIL
+    starts = np.array([[1, 1], [2, 2]])
+    goals = [my_library.RustPoint2(2, 2), my_library.RustPoint2(1, 1)]
+    paths = pixel_map.batch_paths(starts, goals)
+    assert [list(p) for p in paths] == [[(1, 1), (2, 1), (2, 2)], [(2, 2), (2, 1), (1, 1)]]
+
+    # One search from the goal for all units, indexed with [y, x]
+    distances = pixel_map.distance_field([my_library.RustPoint2(2, 2)])
+    assert distances[1, 1] == 2 and distances[0, 0] == np.inf
+    directions = pixel_map.flow_field(my_library.RustPoint2(2, 2))
+    assert tuple(directions[1, 1]) == (1, 0)
+
+    # Weighted cells are avoided without being walls, e.g. around enemy units
+    pixel_map.add_cost_circle((2, 1), 1, 10, falloff="constant")
+    assert pixel_map.costs[1, 2] == 11
+    path = pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    assert path.cost == 11, path.cost
+    pixel_map.remove_cost_circle((2, 1), 1, 10, falloff="constant")
+    pixel_map.set_costs(np.ones((3, 3)))
+    pixel_map.clear_costs()
+
+    # Straight lines over walkable cells
+    assert pixel_map.line_of_sight((1, 1), (2, 1))
+    assert not pixel_map.line_of_sight(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    visible = pixel_map.line_of_sight_many(np.array([[1.0, 1.0], [1.0, 1.0]]), np.array([[2.0, 1.0], [0.0, 0.0]]))
+    assert visible.tolist() == [True, False]
+    assert pixel_map.raycast((1, 1), (1, 0), 10) == (2.5, 1)
+    assert pixel_map.raycast((1, 1), (1, 0), 1) is None
+    for max_dist in [-1, float("nan")]:
+        try:
+            pixel_map.raycast((1, 1), (1, 0), max_dist)
+            assert False, "Function above should throw error on a negative max_dist"
+        except ValueError:
+            pass
+
+    # Any-angle paths only keep the corners, as float points
+    assert pixel_map.any_angle_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2)) == [(1, 1), (2, 1), (2, 2)]
+    assert pixel_map.smooth_path(path) == [(1, 1), (2, 1), (2, 2)]
+
+    # Big units only use cells with enough distance to the closest wall
+    assert pixel_map.clearance.shape == (3, 3) and pixel_map.clearance[0, 0] == 0
+    assert pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), unit_radius=1, raise_not_found=False) is None
+    assert pixel_map.jps_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), unit_radius=1, raise_not_found=False) is None
+    assert pixel_map.astar_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), unit_radius=1, raise_not_found=False) is None
+
+    # A planner per unit repairs its path when the map changes instead of searching again
+    planner = my_library.RustPlanner(pixel_map, my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    assert planner.path().cost == 2
+    pixel_map.set_blocked(2, 1)
+    assert planner.path(raise_not_found=False) is None
+    pixel_map.set_walkable(2, 1)
+    planner.start = my_library.RustPoint2(2, 1)
+    assert planner.path().cost == 1
+
+    # Connected areas answer reachability without a search
+    assert pixel_map.components().tolist() == [[-1, -1, -1], [-1, 0, 0], [-1, -1, 0]]
+    assert pixel_map.component_of(my_library.RustPoint2(2, 2)) == 0
+    assert pixel_map.component_of(my_library.RustPoint2(0, 0)) is None
+    assert pixel_map.is_reachable(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    pixel_map.set_blocked(2, 1)
+    assert not pixel_map.is_reachable(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+    assert pixel_map.component_count == 2
+    pixel_map.set_walkable(2, 1)
+
+    # Hierarchical search for long queries, the abstract graph is cached on the map
+    path = pixel_map.hpa_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2), cluster_size=2)
+    assert path.waypoints == [(1, 1), (2, 1), (2, 2)] and path.cost == 2
+
+    # Regions and the chokes between them, a snapshot of the current map
+    analysis = pixel_map.analyze()
+    assert len(analysis.regions) == 1 and analysis.chokes == []
+    assert analysis.region_at(my_library.RustPoint2(2, 2)).area == 3
+    assert analysis.labels().tolist() == [[-1, -1, -1], [-1, 0, 0], [-1, -1, 0]]
+
+    # Maps and points can be pickled, e.g. to cache them or to send them to other processes
+    restored = pickle.loads(pickle.dumps(pixel_map))
+    assert (restored.width, restored.height) == (pixel_map.width, pixel_map.height)
+    assert restored.component_count == pixel_map.component_count
+    assert copy.deepcopy(my_library.RustPoint2(3, 4)).x == 3
+    assert pickle.loads(pickle.dumps(my_library.PointCollection([my_library.RustPoint2(1, 2)]))).len() == 1
+
+    # MovingAI benchmark maps and scenarios
+    with tempfile.TemporaryDirectory() as directory:
+        map_path = os.path.join(directory, "small.map")
+        scen_path = os.path.join(directory, "small.map.scen")
+        with open(map_path, "w") as f:
+            f.write("type octile\nheight 2\nwidth 3\nmap\n...\n@..\n")
+        with open(scen_path, "w") as f:
+            f.write("version 1\n0\tsmall.map\t3\t2\t0\t0\t2\t1\t2.41421356\n")
+        file_map = my_library.RustPixelMap.from_file(map_path)
+        assert (file_map.width, file_map.height) == (3, 2)
+        result = file_map.run_scenarios(scen_path)[0]
+        assert result.astar_length == 3 and result.jps_length is not None
+        assert abs(result.octile_length - result.optimal_length) < 1e-6
+
+        # Grids of digits like AutomatonLE.txt
+        grid_path = os.path.join(directory, "grid.txt")
+        my_library.save_grid_text(grid_path, np.array([[0, 1], [1, 1]]))
+        assert my_library.load_grid_text(grid_path, flip_y=True).tolist() == [[1, 1], [0, 1]]
+        text_map = my_library.RustPixelMap.from_text(grid_path)
+        text_map.to_text(grid_path)
+        assert my_library.load_grid_text(grid_path).tolist() == [[0, 1], [1, 1]]
+        try:
+            my_library.save_grid_text(grid_path, np.array([[0, 10]]))
+            assert False, "Function above should throw error on a value that is not a digit"
+        except ValueError:
+            pass
+
+        # Compact binary files, the tiles are memory-mapped when loading
+        binary_path = os.path.join(directory, "map.rpm")
+        pixel_map.to_binary(binary_path)
+        binary_map = my_library.RustPixelMap.from_binary(binary_path)
+        assert binary_map.memory_mapped == (os.name == "posix")
+        assert binary_map.component_count == pixel_map.component_count
+        del binary_map
+
+        # Debug pictures with overlays
+        png_path = os.path.join(directory, "map.png")
+        path = pixel_map.find_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+        pixel_map.render_png(png_path, overlays=[pixel_map.components(), path], scale=8)
+        with open(png_path, "rb") as f:
+            assert f.read(8) == b"\x89PNG\r\n\x1a\n"
+        # Tuples are points, e.g. the float corners of any_angle_path, only a RustPath is drawn as a line
+        corners = pixel_map.any_angle_path(my_library.RustPoint2(1, 1), my_library.RustPoint2(2, 2))
+        pixel_map.render_png(png_path, overlays=[corners])
+        try:
+            pixel_map.render_png(png_path, overlays=[[(-1, 0)]])
+            assert False, "Function above should throw error on a negative coordinate"
+        except ValueError:
+            pass
+
+    # One tile per cell, a wrong number of tiles is not padded or cut off
+    try:
+        my_library.RustPixelMap(3, 2, list("....."))
+        assert False, "Function above should throw error on a missing tile"
+    except ValueError:
+        pass
+
     # Test rust structs
     p1 = my_library.RustPoint2(0, 0)
     print(p1)
@@ -144,8 +369,11 @@ def basic_tests():
 
     p4 = my_library.RustPoint2(9, 10)
     closest_point = ps.closest_point(p4)
-    # This should raise a proper error
-    # closest_point = my_library.Point2Collection([]).closest_point(p4)
+    try:
+        my_library.PointCollection([]).closest_point(p4)
+        assert False, "Function above should throw error on an empty collection"
+    except ValueError:
+        pass
     print(f"Closest point: {closest_point}")
 
     print(ps, type(ps))
@@ -1,6 +1,316 @@
-#![feature(cell_update)]
-// Testing and benchmark crate
+// Testing and benchmark crate, doubles as a command line tool to inspect maps without python
+// Uses the python free part of the library from the pathing crate
 #![feature(test)]
 extern crate test;
 
-fn main() {}
+use std::env;
+use std::process;
+
+use movingai::{Coords2D, Map2D};
+
+use pathing::components::Components;
+use pathing::formats;
+use pathing::grid::BitGrid;
+use pathing::jps;
+use pathing::pathfinding::{self, SearchLimits, SearchOptions};
+use pathing::render::{Canvas, Overlay};
+use pathing::state::MapState;
+
+const USAGE: &str = "Usage: my_library <map file> [--flip-y] <command>
+
+Map files are MovingAI '.map' files, files of RustPixelMap.to_binary or grids of digits like AutomatonLE.txt
+where 1 is walkable
+--flip-y reads the last line of a digit grid as row 0
+
+Commands:
+    info                                    size, walkable ratio and number of connected areas
+    path <x1> <y1> <x2> <y2> [--jps]        route as 'x, y' lines, the length goes to stderr
+    render [<x1> <y1> <x2> <y2>] [--jps]    ASCII picture of the map with the route
+        [--png <file>] [--scale <pixels>]   writes a PNG picture instead, 4 pixels per cell by default
+
+A* moves in 8 directions without cutting corners, --jps uses JPS which cuts corners";
+
+/// Corner points or cells of the route from start to goal and its length
+fn route(
+    map: &BitGrid,
+    start: Coords2D,
+    goal: Coords2D,
+    jps: bool,
+) -> Result<(Vec<Coords2D>, f64), String> {
+    for &point in [start, goal].iter() {
+        if map.is_out_of_bound(point) {
+            return Err(format!(
+                "Point {:?} is outside of the map of size {}x{}",
+                point,
+                map.width(),
+                map.height()
+            ));
+        }
+        if !map.is_traversable(point) {
+            return Err(format!("Point {:?} is not walkable", point));
+        }
+    }
+    let path = if jps {
+        jps::jps(map, start, goal)
+    } else {
+        let options = SearchOptions::default();
+        pathfinding::astar(map, None, start, goal, &options, &SearchLimits::default())
+    };
+    if !path.reached_goal {
+        return Err(format!("No path from {:?} to {:?}", start, goal));
+    }
+    Ok((path.waypoints, path.cost))
+}
+
+/// Every cell on the straight or diagonal lines between the corner points
+fn cells_of(corners: &[Coords2D]) -> Vec<Coords2D> {
+    let mut cells = corners.first().cloned().into_iter().collect::<Vec<_>>();
+    for pair in corners.windows(2) {
+        let (mut x, mut y) = (pair[0].0 as isize, pair[0].1 as isize);
+        let (to_x, to_y) = (pair[1].0 as isize, pair[1].1 as isize);
+        while (x, y) != (to_x, to_y) {
+            x += (to_x - x).signum();
+            y += (to_y - y).signum();
+            cells.push((x as usize, y as usize));
+        }
+    }
+    cells
+}
+
+fn load(path: &str, flip_y: bool) -> Result<BitGrid, String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("Can not read {}: {}", path, error))?;
+    if bytes.starts_with(b"RPM") {
+        let state = MapState::decode(&bytes)?;
+        return BitGrid::from_bytes(state.width, state.height, state.walkable);
+    }
+    let contents = String::from_utf8(bytes).map_err(|_| format!("{} is not a text file", path))?;
+    if path.ends_with(".map") || contents.starts_with("type") {
+        let (width, height, tiles) = formats::parse_map(&contents)?;
+        let walkable: Vec<bool> = tiles.iter().map(|&tile| tile == '.').collect();
+        return Ok(BitGrid::new(width, height, &walkable));
+    }
+    let (width, height, values) = formats::parse_grid(&contents, flip_y)?;
+    let walkable: Vec<bool> = values.iter().map(|&value| value == 1).collect();
+    Ok(BitGrid::new(width, height, &walkable))
+}
+
+fn info(map: &BitGrid) -> String {
+    let (free, cells) = (map.free_states(), map.width() * map.height());
+    format!(
+        "size: {}x{}\nwalkable: {} of {} cells ({:.1}%)\nconnected areas: {}",
+        map.width(),
+        map.height(),
+        free,
+        cells,
+        100.0 * free as f64 / cells.max(1) as f64,
+        Components::new(map).count()
+    )
+}
+
+/// '#' for walls, '.' for walkable cells, '*' for the route and 'S' and 'G' for its ends
+fn render(map: &BitGrid, route: &[Coords2D]) -> String {
+    let mut rows: Vec<Vec<char>> = (0..map.height())
+        .map(|y| {
+            (0..map.width())
+                .map(|x| if map.is_traversable((x, y)) { '.' } else { '#' })
+                .collect()
+        })
+        .collect();
+    for &(x, y) in cells_of(route).iter() {
+        rows[y][x] = '*';
+    }
+    if let (Some(&(x1, y1)), Some(&(x2, y2))) = (route.first(), route.last()) {
+        rows[y1][x1] = 'S';
+        rows[y2][x2] = 'G';
+    }
+    rows.into_iter()
+        .map(|row| row.into_iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Same picture as 'render' as PNG, with the connected areas in different colors below the route
+fn render_png(map: &BitGrid, route: &[Coords2D], scale: usize) -> Vec<u8> {
+    let mut canvas = Canvas::new(map);
+    let labels = Components::new(map).labels().to_vec();
+    canvas
+        .draw(&Overlay::Labels(labels), [0, 0, 0])
+        .and_then(|_| canvas.draw(&Overlay::Path(route.to_vec()), [255, 255, 255]))
+        .expect("Labels have one value per cell");
+    canvas.to_png(scale)
+}
+
+fn parse_points(args: &[String]) -> Result<(Coords2D, Coords2D), String> {
+    let numbers = args
+        .iter()
+        .map(|arg| {
+            arg.parse::<usize>()
+                .map_err(|_| format!("Expected a coordinate, got '{}'", arg))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+    match numbers.as_slice() {
+        &[x1, y1, x2, y2] => Ok(((x1, y1), (x2, y2))),
+        _ => Err(format!("Expected 4 coordinates, got {}", numbers.len())),
+    }
+}
+
+/// Options that 'run' understands, the last two take a value
+const OPTIONS: [&str; 4] = ["--flip-y", "--jps", "--png", "--scale"];
+
+/// Output of the command for stdout and stderr
+fn run(args: &[String]) -> Result<(String, String), String> {
+    if let Some(unknown) = args
+        .iter()
+        .find(|arg| arg.starts_with("--") && !OPTIONS.contains(&arg.as_str()))
+    {
+        return Err(format!("Unknown option '{}'\n\n{}", unknown, USAGE));
+    }
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+    let (flip_y, jps) = (flag("--flip-y"), flag("--jps"));
+    // Index of the value that follows an option like '--png out.png'
+    let option = |name: &str| -> Result<Option<usize>, String> {
+        match args.iter().position(|arg| arg == name) {
+            Some(index) if index + 1 < args.len() => Ok(Some(index + 1)),
+            Some(_) => Err(format!("{} needs a value", name)),
+            None => Ok(None),
+        }
+    };
+    let (png, scale) = (option("--png")?, option("--scale")?);
+    let values = [png, scale];
+    let scale = match scale {
+        Some(index) => args[index]
+            .parse::<usize>()
+            .map_err(|_| format!("Expected a number of pixels, got '{}'", args[index]))?,
+        None => 4,
+    };
+    let png = png.map(|index| &args[index]);
+    let args: Vec<String> = args
+        .iter()
+        .enumerate()
+        .filter(|(index, arg)| !arg.starts_with("--") && !values.contains(&Some(*index)))
+        .map(|(_, arg)| arg.clone())
+        .collect();
+    let (path, command, rest) = match args.as_slice() {
+        [path, command, rest @ ..] => (path, command.as_str(), rest),
+        _ => return Err(USAGE.to_string()),
+    };
+    let map = load(path, flip_y)?;
+    match (command, rest.len()) {
+        ("info", 0) => Ok((info(&map), String::new())),
+        ("path", 4) => {
+            let (start, goal) = parse_points(rest)?;
+            let (route, length) = route(&map, start, goal, jps)?;
+            let lines: Vec<String> = cells_of(&route)
+                .iter()
+                .map(|(x, y)| format!("{}, {}", x, y))
+                .collect();
+            Ok((lines.join("\n"), format!("length: {}", length)))
+        }
+        ("render", 0) | ("render", 4) => {
+            let (route, summary) = if rest.is_empty() {
+                (vec![], String::new())
+            } else {
+                let (start, goal) = parse_points(rest)?;
+                let (route, length) = route(&map, start, goal, jps)?;
+                (route, format!("length: {}", length))
+            };
+            match png {
+                Some(file) => {
+                    let picture = render_png(&map, &route, scale);
+                    std::fs::write(file, picture)
+                        .map_err(|error| format!("Can not write {}: {}", file, error))?;
+                    Ok((format!("wrote {}", file), summary))
+                }
+                None => Ok((render(&map, &route), summary)),
+            }
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok((output, summary)) => {
+            println!("{}", output);
+            if !summary.is_empty() {
+                eprintln!("{}", summary);
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test::Bencher;
+
+    #[bench]
+    fn bench_cli_automaton(b: &mut Bencher) {
+        b.iter(|| {
+            let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+            let (output, _) = run(&args("AutomatonLE.txt info")).unwrap();
+            assert!(output.starts_with("size: 184x192\nwalkable: 16300 of 35328 cells"));
+
+            let (output, summary) = run(&args("AutomatonLE.txt path 32 51 150 129")).unwrap();
+            assert!(output.starts_with("32, 51\n") && output.ends_with("\n150, 129"));
+            assert!(summary.starts_with("length: "));
+            let (jps_output, _) = run(&args("AutomatonLE.txt path 32 51 150 129 --jps")).unwrap();
+            assert!(jps_output.ends_with("\n150, 129"));
+
+            let (picture, _) = run(&args("AutomatonLE.txt render 32 51 150 129")).unwrap();
+            assert_eq!(picture.lines().count(), 192);
+            assert_eq!(picture.matches('S').count(), 1);
+            assert_eq!(picture.matches('G').count(), 1);
+
+            let file = std::env::temp_dir().join("my_library_cli_render.png");
+            let command = format!(
+                "AutomatonLE.txt render 32 51 150 129 --png {} --scale 2",
+                file.display()
+            );
+            run(&args(&command)).unwrap();
+            let picture = std::fs::read(&file).unwrap();
+            std::fs::remove_file(&file).unwrap();
+            assert_eq!(&picture[..4], b"\x89PNG");
+            assert_eq!(&picture[16..24], &[0, 0, 1, 112, 0, 0, 1, 128]);
+            assert!(run(&args("AutomatonLE.txt render --png")).is_err());
+
+            assert!(run(&args("AutomatonLE.txt path 0 0 150 129")).is_err());
+            assert!(run(&args("AutomatonLE.txt teleport")).is_err());
+            let error = run(&args("AutomatonLE.txt path 32 51 150 129 --jsp")).unwrap_err();
+            assert!(error.starts_with("Unknown option '--jsp'\n\nUsage: "));
+        });
+    }
+
+    #[bench]
+    fn bench_cli_render(b: &mut Bencher) {
+        b.iter(|| {
+            let map = BitGrid::new(4, 3, &[true; 12]);
+            let (cells, length) = route(&map, (0, 0), (3, 2), false).unwrap();
+            assert!((length - (1.0 + 2.0 * std::f64::consts::SQRT_2)).abs() < 1e-9);
+            assert_eq!(cells_of(&cells).len(), 4);
+            // JPS along the border, which the old blitz-path search needed a padded map for
+            let (corners, jps_length) = route(&map, (0, 2), (3, 0), true).unwrap();
+            assert!((jps_length - length).abs() < 1e-9);
+            assert_eq!((corners[0], corners[corners.len() - 1]), ((0, 2), (3, 0)));
+            assert_eq!(render(&map, &cells).lines().count(), 3);
+            assert_eq!(render(&map, &[(0, 0), (3, 0)]), "S**G\n....\n....");
+
+            let file = std::env::temp_dir().join("my_library_cli_map.rpm");
+            let state = MapState {
+                width: 4,
+                height: 3,
+                walkable: BitGrid::new(4, 3, &[true; 12]).bytes().to_vec(),
+                weights: None,
+            };
+            std::fs::write(&file, state.encode()).unwrap();
+            let (output, _) = run(&[file.display().to_string(), "info".to_string()]).unwrap();
+            std::fs::remove_file(&file).unwrap();
+            assert!(output.starts_with("size: 4x3\nwalkable: 12 of 12 cells"));
+        });
+    }
+}
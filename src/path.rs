@@ -0,0 +1,270 @@
+// Result of a path query that is handed to python
+
+use pyo3::prelude::*;
+use pyo3::{PyIterProtocol, PyObjectProtocol, PySequenceProtocol};
+
+use std::str::FromStr;
+
+use blitz_path::Route;
+use movingai::Coords2D;
+use ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2};
+
+use crate::search::SearchResult;
+
+#[pyclass(name = "RustPath")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustPath {
+    /// Ordered from start to goal, both included
+    #[pyo3(get)]
+    pub waypoints: Vec<Coords2D>,
+    /// Total cost to travel along the waypoints, infinite if no path was found
+    #[pyo3(get)]
+    pub cost: f64,
+    /// Number of nodes taken from the open list, None if the underlying search does not report it
+    #[pyo3(get)]
+    pub nodes_expanded: Option<usize>,
+    /// False if the goal could not be reached and the waypoints only lead towards it
+    #[pyo3(get)]
+    pub reached_goal: bool,
+    /// Work done by the search, None unless search_stats of the map is enabled
+    #[pyo3(get)]
+    pub stats: Option<RustSearchStats>,
+}
+
+impl From<SearchResult> for RustPath {
+    fn from(result: SearchResult) -> Self {
+        RustPath {
+            waypoints: result.waypoints,
+            cost: result.cost,
+            nodes_expanded: result.nodes_expanded,
+            reached_goal: result.reached_goal,
+            stats: None,
+        }
+    }
+}
+
+impl RustPath {
+    pub fn new(waypoints: Vec<Coords2D>, cost: f64, nodes_expanded: Option<usize>) -> Self {
+        RustPath {
+            waypoints,
+            cost,
+            nodes_expanded,
+            reached_goal: true,
+            stats: None,
+        }
+    }
+
+    /// No path exists between start and goal
+    pub fn not_found(nodes_expanded: Option<usize>) -> Self {
+        RustPath {
+            waypoints: vec![],
+            cost: f64::INFINITY,
+            nodes_expanded,
+            reached_goal: false,
+            stats: None,
+        }
+    }
+
+    /// blitz-path returns the steps goal first and no steps at all if start and goal are the same
+    pub fn from_route(route: Option<Route>, start: Coords2D) -> Self {
+        match route {
+            Some(route) => {
+                let mut waypoints = route.steps();
+                waypoints.reverse();
+                if waypoints.is_empty() {
+                    waypoints.push(start);
+                }
+                RustPath::new(waypoints, route.distance(), None)
+            }
+            None => RustPath::not_found(None),
+        }
+    }
+}
+
+fn segment_length(a: Coords2D, b: Coords2D) -> f64 {
+    let dx = a.0 as f64 - b.0 as f64;
+    let dy = a.1 as f64 - b.1 as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[pymethods]
+impl RustPath {
+    pub fn point_at_distance(&self, distance: f64) -> Option<(f64, f64)> {
+        /// Point reached after walking 'distance' along the waypoints, clamped to the start and the end of the path
+        /// Returns None on an empty path
+        let first = *self.waypoints.first()?;
+        let mut remaining = distance.max(0.0);
+        for pair in self.waypoints.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let length = segment_length(a, b);
+            if remaining <= length && length > 0.0 {
+                let t = remaining / length;
+                return Some((
+                    a.0 as f64 + (b.0 as f64 - a.0 as f64) * t,
+                    a.1 as f64 + (b.1 as f64 - a.1 as f64) * t,
+                ));
+            }
+            remaining -= length;
+        }
+        let last = *self.waypoints.last().unwrap_or(&first);
+        Some((last.0 as f64, last.1 as f64))
+    }
+
+    pub fn to_numpy<'py>(&self, py: Python<'py>) -> &'py PyArray2<i64> {
+        /// Waypoints as an array of shape (len, 2) with x in the first and y in the second column
+        let flat: Vec<i64> = self
+            .waypoints
+            .iter()
+            .flat_map(|&(x, y)| vec![x as i64, y as i64])
+            .collect();
+        Array2::from_shape_vec((self.waypoints.len(), 2), flat)
+            .unwrap()
+            .into_pyarray(py)
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for RustPath {
+    fn __len__(&self) -> usize {
+        self.waypoints.len()
+    }
+
+    fn __getitem__(&self, index: isize) -> PyResult<Coords2D> {
+        let len = self.waypoints.len() as isize;
+        let position = if index < 0 { index + len } else { index };
+        if position < 0 || position >= len {
+            return Err(pyo3::exceptions::PyIndexError::new_err(
+                "RustPath index out of range",
+            ));
+        }
+        Ok(self.waypoints[position as usize])
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for RustPath {
+    fn __iter__(slf: PyRef<Self>) -> PyResult<Py<RustPathIterator>> {
+        let iterator = RustPathIterator {
+            waypoints: slf.waypoints.clone().into_iter(),
+        };
+        Py::new(slf.py(), iterator)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustPath {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustPath(len: {}, cost: {}, reached_goal: {})",
+            self.waypoints.len(),
+            self.cost,
+            self.reached_goal
+        ))
+    }
+    fn __str__(&self) -> PyResult<String> {
+        Ok(format!("RustPath({:?})", self.waypoints))
+    }
+}
+
+/// Which statistics the path queries of a map collect
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StatsMode {
+    Off,
+    /// Counts and wall time
+    Stats,
+    /// Counts, wall time and the expanded tiles in order
+    Trace,
+}
+
+impl StatsMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            StatsMode::Off => "off",
+            StatsMode::Stats => "stats",
+            StatsMode::Trace => "trace",
+        }
+    }
+}
+
+impl FromStr for StatsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(StatsMode::Off),
+            "stats" => Ok(StatsMode::Stats),
+            "trace" => Ok(StatsMode::Trace),
+            _ => Err(format!(
+                "Unknown search stats mode '{}', expected one of: off, stats, trace",
+                s
+            )),
+        }
+    }
+}
+
+/// Statistics of one path query, summed over all searches the query ran
+/// For hpa_path only the search on the abstract graph is counted, like in nodes_expanded of the path
+#[pyclass(name = "RustSearchStats")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustSearchStats {
+    /// Nodes taken from the open list
+    #[pyo3(get)]
+    pub nodes_expanded: usize,
+    /// Nodes pushed to the open list, a node counts again whenever a cheaper way to it is found
+    #[pyo3(get)]
+    pub nodes_generated: usize,
+    /// Largest number of entries on the open list at once
+    #[pyo3(get)]
+    pub open_list_peak: usize,
+    /// Wall time of the query in seconds
+    #[pyo3(get)]
+    pub seconds: f64,
+    /// Expanded tiles in order, only recorded in the "trace" mode
+    pub trace: Option<Vec<Coords2D>>,
+}
+
+#[pymethods]
+impl RustSearchStats {
+    #[getter]
+    fn trace<'py>(&self, py: Python<'py>) -> Option<&'py PyArray2<i64>> {
+        /// Expanded tiles as an array of shape (n, 2) with x in the first and y in the second column, in the
+        /// order the search expanded them, None unless search_stats was "trace"
+        let trace = self.trace.as_ref()?;
+        let flat: Vec<i64> = trace
+            .iter()
+            .flat_map(|&(x, y)| vec![x as i64, y as i64])
+            .collect();
+        Some(
+            Array2::from_shape_vec((trace.len(), 2), flat)
+                .unwrap()
+                .into_pyarray(py),
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustSearchStats {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustSearchStats(expanded: {}, generated: {}, open_list_peak: {}, seconds: {})",
+            self.nodes_expanded, self.nodes_generated, self.open_list_peak, self.seconds
+        ))
+    }
+}
+
+#[pyclass(name = "RustPathIterator")]
+pub struct RustPathIterator {
+    waypoints: std::vec::IntoIter<Coords2D>,
+}
+
+#[pyproto]
+impl PyIterProtocol for RustPathIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> Option<Coords2D> {
+        slf.waypoints.next()
+    }
+}
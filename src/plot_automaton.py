@@ -38,6 +38,21 @@ def get_map_specific_bots() -> Iterable[BotAI]:
         yield bot
 
 
+def export_binary_maps(target_folder: str) -> List[str]:
+    """Write the pathing grid of every map in pickle_data with RustPixelMap.to_binary
+    Unpickling the bots is slow, the binary files can be loaded memory-mapped with RustPixelMap.from_binary"""
+    import my_library
+
+    os.makedirs(target_folder, exist_ok=True)
+    paths = []
+    for bot in get_map_specific_bots():
+        pixel_map = my_library.RustPixelMap.from_numpy(bot.game_info.pathing_grid.data_numpy)
+        path = os.path.join(target_folder, f"{bot.game_info.map_name}.rpm")
+        pixel_map.to_binary(path)
+        paths.append(path)
+    return paths
+
+
 # Global bot object that is used in TestClass.test_position_*
 bot_object_generator = get_map_specific_bots()
 # random_bot_object: BotAI = next(bot_object_generator)
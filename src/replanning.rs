@@ -0,0 +1,488 @@
+// Incremental replanning with D* Lite
+// The planner keeps its search tree between queries, so after the map changes or the unit moves
+// only the part of the tree that is affected by the change is repaired instead of searching from scratch
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use pyo3::prelude::*;
+use pyo3::PyObjectProtocol;
+
+use movingai::Coords2D;
+use movingai::Map2D;
+
+use crate::costs::CostGrid;
+use crate::error::PathError;
+use crate::path::RustPath;
+use crate::pathfinding::{neighbors, SearchOptions};
+use crate::{parse_search_options, path_or_none, RustPixelMap, RustPoint2};
+
+/// Priority of a vertex, compared lexicographically
+type Key = (f64, f64);
+
+#[derive(Copy, Clone, Debug)]
+struct QueueEntry {
+    key: Key,
+    index: usize,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max heap: lowest key first
+        compare_keys(other.key, self.key)
+    }
+}
+
+fn compare_keys(a: Key, b: Key) -> Ordering {
+    a.0.partial_cmp(&b.0)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+}
+
+/// Rectangle of tiles that changed, 'reach' is 1 if walls changed and 0 if only weights changed
+/// Changed walls also change the clearance up to the unit radius around them
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Change {
+    pub corner: Coords2D,
+    pub far_corner: Coords2D,
+    pub reach: f64,
+}
+
+/// Recent changes of a RustPixelMap, planners only read the entries since the version they last planned on
+#[derive(Clone, Debug, Default)]
+pub struct ChangeLog {
+    /// Version before the oldest kept entry, older entries are dropped
+    first: u64,
+    entries: VecDeque<Change>,
+}
+
+impl ChangeLog {
+    /// Planners that fall further behind search from scratch again
+    pub const CAPACITY: usize = 1024;
+
+    /// Number of changes so far
+    pub fn version(&self) -> u64 {
+        self.first + self.entries.len() as u64
+    }
+
+    pub fn push(&mut self, corner: Coords2D, far_corner: Coords2D, reach: f64) {
+        if self.entries.len() == ChangeLog::CAPACITY {
+            self.entries.pop_front();
+            self.first += 1;
+        }
+        self.entries.push_back(Change {
+            corner,
+            far_corner,
+            reach,
+        });
+    }
+
+    /// Changes after the given version, None if some of them were already dropped
+    pub fn since(&self, version: u64) -> Option<impl Iterator<Item = &Change>> {
+        if version < self.first || version > self.version() {
+            return None;
+        }
+        Some(self.entries.iter().skip((version - self.first) as usize))
+    }
+}
+
+/// D* Lite search from the goal towards the start
+/// g and rhs hold the cost to reach the goal, the start may move between queries
+#[derive(Clone, Debug)]
+pub struct DStarLite {
+    options: SearchOptions,
+    width: usize,
+    height: usize,
+    start: Coords2D,
+    goal: Coords2D,
+    /// Start at the time of the last repair, the heuristic offset 'km' grows when the start moves
+    last_start: Coords2D,
+    km: f64,
+    g: Vec<f64>,
+    rhs: Vec<f64>,
+    /// Key under which a tile is currently queued, None if it is not in the open list
+    queued: Vec<Option<Key>>,
+    open: BinaryHeap<QueueEntry>,
+    /// Version of the change log the search tree was last repaired for, None before the first search
+    version: Option<u64>,
+    nodes_expanded: usize,
+}
+
+impl DStarLite {
+    pub fn new(
+        width: usize,
+        height: usize,
+        start: Coords2D,
+        goal: Coords2D,
+        options: SearchOptions,
+    ) -> Self {
+        let size = width * height;
+        DStarLite {
+            options,
+            width,
+            height,
+            start,
+            goal,
+            last_start: start,
+            km: 0.0,
+            g: vec![f64::INFINITY; size],
+            rhs: vec![f64::INFINITY; size],
+            queued: vec![None; size],
+            open: BinaryHeap::new(),
+            version: None,
+            nodes_expanded: 0,
+        }
+    }
+
+    pub fn start(&self) -> Coords2D {
+        self.start
+    }
+
+    pub fn goal(&self) -> Coords2D {
+        self.goal
+    }
+
+    pub fn unit_radius(&self) -> f64 {
+        self.options.unit_radius
+    }
+
+    /// The unit moved, the search tree stays valid
+    pub fn set_start(&mut self, start: Coords2D) {
+        self.start = start;
+    }
+
+    /// A new goal invalidates the whole search tree
+    pub fn set_goal(&mut self, goal: Coords2D) {
+        *self = DStarLite::new(self.width, self.height, self.start, goal, self.options);
+    }
+
+    fn index(&self, tile: Coords2D) -> usize {
+        tile.1 * self.width + tile.0
+    }
+
+    fn tile(&self, index: usize) -> Coords2D {
+        (index % self.width, index / self.width)
+    }
+
+    fn calculate_key(&self, index: usize) -> Key {
+        let best = self.g[index].min(self.rhs[index]);
+        let h = self
+            .options
+            .heuristic
+            .distance(self.start, self.tile(index));
+        (best + h + self.km, best)
+    }
+
+    fn push(&mut self, index: usize) {
+        let key = self.calculate_key(index);
+        self.queued[index] = Some(key);
+        self.open.push(QueueEntry { key, index });
+    }
+
+    /// Drops outdated heap entries, returns the lowest queued key
+    fn top_key(&mut self) -> Key {
+        while let Some(&entry) = self.open.peek() {
+            if self.queued[entry.index] == Some(entry.key) {
+                return entry.key;
+            }
+            self.open.pop();
+        }
+        (f64::INFINITY, f64::INFINITY)
+    }
+
+    /// Successors of a tile and the cost to step there
+    fn successors<U, T: Map2D<U>>(
+        &self,
+        map: &T,
+        costs: Option<&CostGrid>,
+        tile: Coords2D,
+    ) -> Vec<(usize, f64)> {
+        if !map.is_traversable(tile) {
+            return vec![];
+        }
+        neighbors(map, tile, &self.options)
+            .into_iter()
+            .map(|(next, step_cost)| {
+                let index = self.index(next);
+                let weight = costs.map_or(CostGrid::MIN_WEIGHT, |costs| costs.weight(index));
+                (index, step_cost * weight)
+            })
+            .collect()
+    }
+
+    fn update_vertex<U, T: Map2D<U>>(&mut self, map: &T, costs: Option<&CostGrid>, index: usize) {
+        if index != self.index(self.goal) {
+            let tile = self.tile(index);
+            self.rhs[index] = self
+                .successors(map, costs, tile)
+                .into_iter()
+                .map(|(next, step_cost)| step_cost + self.g[next])
+                .fold(f64::INFINITY, f64::min);
+        }
+        self.queued[index] = None;
+        if self.g[index] != self.rhs[index] {
+            self.push(index);
+        }
+    }
+
+    /// Tiles whose outgoing edges may use a tile of the changed rectangle, including the rectangle itself
+    /// Diagonal steps depend on the corners as well, so one more ring of tiles is affected
+    fn affected_by(&self, change: &Change) -> impl Iterator<Item = usize> {
+        let margin = (change.reach * (self.options.unit_radius + 0.5)).ceil() as usize + 1;
+        let (width, height) = (self.width, self.height);
+        let (x0, y0) = (
+            change.corner.0.saturating_sub(margin),
+            change.corner.1.saturating_sub(margin),
+        );
+        let x1 = (change.far_corner.0 + margin).min(width - 1);
+        let y1 = (change.far_corner.1 + margin).min(height - 1);
+        (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| y * width + x))
+    }
+
+    fn compute_shortest_path<U, T: Map2D<U>>(&mut self, map: &T, costs: Option<&CostGrid>) {
+        let start = self.index(self.start);
+        loop {
+            let top = self.top_key();
+            let start_key = self.calculate_key(start);
+            if compare_keys(top, start_key) != Ordering::Less && self.rhs[start] <= self.g[start] {
+                break;
+            }
+            let entry = match self.open.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            let index = entry.index;
+            let new_key = self.calculate_key(index);
+            if compare_keys(entry.key, new_key) == Ordering::Less {
+                self.push(index);
+                continue;
+            }
+            self.queued[index] = None;
+            self.nodes_expanded += 1;
+            let tile = self.tile(index);
+            // Edges are symmetric apart from the weight of the entered tile, so predecessors are the neighbours
+            let predecessors: Vec<usize> = neighbors(map, tile, &self.options)
+                .into_iter()
+                .map(|(previous, _)| self.index(previous))
+                .collect();
+            if self.g[index] > self.rhs[index] {
+                self.g[index] = self.rhs[index];
+            } else {
+                self.g[index] = f64::INFINITY;
+                self.update_vertex(map, costs, index);
+            }
+            for previous in predecessors {
+                self.update_vertex(map, costs, previous);
+            }
+        }
+    }
+
+    /// Repairs the search tree after changes of the map or the start and returns the path from start to goal
+    /// Only the changes logged since the last call are repaired, nodes_expanded only counts the work of this call
+    pub fn plan<U, T: Map2D<U>>(
+        &mut self,
+        map: &T,
+        costs: Option<&CostGrid>,
+        changes: &ChangeLog,
+    ) -> Result<RustPath, PathError> {
+        if map.width() != self.width || map.height() != self.height {
+            *self = DStarLite::new(
+                map.width(),
+                map.height(),
+                self.start,
+                self.goal,
+                self.options,
+            );
+        }
+        for &point in [self.start, self.goal].iter() {
+            if map.is_out_of_bound(point) {
+                return Err(PathError::OutOfBounds {
+                    point,
+                    width: self.width,
+                    height: self.height,
+                });
+            }
+        }
+        self.nodes_expanded = 0;
+
+        // Too many changes to repair, the search starts over
+        if let Some(version) = self.version {
+            if changes.since(version).is_none() {
+                *self =
+                    DStarLite::new(self.width, self.height, self.start, self.goal, self.options);
+            }
+        }
+        match self.version {
+            None => {
+                let goal = self.index(self.goal);
+                self.rhs[goal] = 0.0;
+                self.push(goal);
+            }
+            Some(version) => {
+                self.km += self.options.heuristic.distance(self.last_start, self.start);
+                self.last_start = self.start;
+                let mut affected: Vec<usize> = changes
+                    .since(version)
+                    .unwrap()
+                    .flat_map(|change| self.affected_by(change))
+                    .collect();
+                affected.sort_unstable();
+                affected.dedup();
+                for index in affected {
+                    self.update_vertex(map, costs, index);
+                }
+            }
+        }
+        self.version = Some(changes.version());
+
+        for &point in [self.start, self.goal].iter() {
+            if !map.is_traversable(point) {
+                return Err(PathError::Blocked(point));
+            }
+        }
+        self.compute_shortest_path(map, costs);
+        self.extract_path(map, costs)
+    }
+
+    fn extract_path<U, T: Map2D<U>>(
+        &self,
+        map: &T,
+        costs: Option<&CostGrid>,
+    ) -> Result<RustPath, PathError> {
+        let not_found = PathError::NotFound {
+            start: self.start,
+            goal: self.goal,
+        };
+        let goal = self.index(self.goal);
+        let mut current = self.index(self.start);
+        // The start may be left overconsistent, its rhs value is already the cost of the path
+        if self.rhs[current].is_infinite() && current != goal {
+            return Err(not_found);
+        }
+        let mut waypoints = vec![self.start];
+        let mut cost = 0.0;
+        while current != goal {
+            // Greedily follow the cheapest successor, the g values lead to the goal
+            let (next, step_cost) = self
+                .successors(map, costs, self.tile(current))
+                .into_iter()
+                .min_by(|a, b| {
+                    (a.1 + self.g[a.0])
+                        .partial_cmp(&(b.1 + self.g[b.0]))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .ok_or(not_found)?;
+            if self.g[next].is_infinite() || waypoints.len() > self.g.len() {
+                return Err(not_found);
+            }
+            cost += step_cost;
+            current = next;
+            waypoints.push(self.tile(current));
+        }
+        Ok(RustPath::new(waypoints, cost, Some(self.nodes_expanded)))
+    }
+}
+
+/// Persistent planner for one unit on a RustPixelMap
+/// Call path() again after the map was changed with set_blocked / set_walkable or the unit moved
+#[pyclass(name = "RustPlanner")]
+pub struct RustPlanner {
+    pixel_map: Py<RustPixelMap>,
+    planner: DStarLite,
+}
+
+#[pymethods]
+impl RustPlanner {
+    #[new]
+    #[args(
+        diagonal = "true",
+        corner_cutting = "\"never\"",
+        heuristic = "None",
+        unit_radius = "0.0"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        pixel_map: Py<RustPixelMap>,
+        start_pos: RustPoint2,
+        goal_pos: RustPoint2,
+        diagonal: bool,
+        corner_cutting: &str,
+        heuristic: Option<&str>,
+        unit_radius: f64,
+    ) -> PyResult<Self> {
+        /// diagonal, corner_cutting, heuristic and unit_radius work like in RustPixelMap.find_path
+        let (_, options) =
+            parse_search_options("astar", diagonal, corner_cutting, heuristic, unit_radius)?;
+        let (width, height) = {
+            let map = pixel_map.borrow(py);
+            (map.width(), map.height())
+        };
+        let planner = DStarLite::new(
+            width,
+            height,
+            start_pos.to_coords_2d(),
+            goal_pos.to_coords_2d(),
+            options,
+        );
+        Ok(RustPlanner { pixel_map, planner })
+    }
+
+    #[getter]
+    fn start(&self) -> RustPoint2 {
+        let (x, y) = self.planner.start();
+        RustPoint2::new(x, y)
+    }
+
+    #[setter]
+    fn set_start(&mut self, pos: RustPoint2) {
+        /// Moving the start keeps the search tree, e.g. when the unit walked along the path
+        self.planner.set_start(pos.to_coords_2d());
+    }
+
+    #[getter]
+    fn goal(&self) -> RustPoint2 {
+        let (x, y) = self.planner.goal();
+        RustPoint2::new(x, y)
+    }
+
+    #[setter]
+    fn set_goal(&mut self, pos: RustPoint2) {
+        /// A new goal starts the search from scratch
+        self.planner.set_goal(pos.to_coords_2d());
+    }
+
+    #[args(raise_not_found = "true")]
+    fn path(&mut self, py: Python, raise_not_found: bool) -> PyResult<Option<RustPath>> {
+        /// Repairs the previous search and returns the path from start to goal
+        /// Raises the same errors as RustPixelMap.find_path
+        let pixel_map = self.pixel_map.borrow(py);
+        let path = pixel_map.rust_plan(&mut self.planner);
+        path_or_none(path, raise_not_found)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustPlanner {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RustPlanner(start: {:?}, goal: {:?})",
+            self.planner.start(),
+            self.planner.goal()
+        ))
+    }
+}
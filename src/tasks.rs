@@ -0,0 +1,92 @@
+// Path queries for asyncio
+// The search runs on the rayon thread pool without the GIL and its result is handed back to the event loop
+// with call_soon_threadsafe, so a slow query does not block the coroutine that is running at the time
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use crate::error::PathError;
+use crate::path::RustPath;
+use crate::path_or_none;
+
+/// Done callback of the future, a query that is cancelled before a worker picked it up is skipped
+#[pyclass]
+struct CancelOnDone {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl CancelOnDone {
+    #[call]
+    fn __call__(&self, future: &PyAny) -> PyResult<()> {
+        if future.call_method0("cancelled")?.is_true()? {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Hands the result of a query to its future, called by the event loop on its own thread
+#[pyclass]
+struct Resolve {
+    path: Option<Result<RustPath, PathError>>,
+    raise_not_found: bool,
+}
+
+#[pymethods]
+impl Resolve {
+    #[call]
+    fn __call__(&mut self, py: Python, future: &PyAny) -> PyResult<()> {
+        let path = match self.path.take() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // The future was cancelled while the search was running
+        if future.call_method0("done")?.is_true()? {
+            return Ok(());
+        }
+        match path_or_none(path, self.raise_not_found) {
+            Ok(path) => future.call_method1("set_result", (path,))?,
+            Err(error) => future.call_method1("set_exception", (error.into_instance(py),))?,
+        };
+        Ok(())
+    }
+}
+
+/// Runs 'search' on the thread pool and returns an asyncio future of the running event loop for its result
+/// The future resolves like find_path: to the path, to None or with the exception of the query
+pub fn spawn<F>(py: Python, raise_not_found: bool, search: F) -> PyResult<PyObject>
+where
+    F: FnOnce() -> Result<RustPath, PathError> + Send + 'static,
+{
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let future = event_loop.call_method0("create_future")?;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let on_done = CancelOnDone {
+        cancelled: cancelled.clone(),
+    };
+    future.call_method1("add_done_callback", (Py::new(py, on_done)?,))?;
+
+    let event_loop: PyObject = event_loop.into();
+    let handle: PyObject = future.into();
+    let result = handle.clone_ref(py);
+    rayon::spawn(move || {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        let path = search();
+        Python::with_gil(|py| {
+            let resolve = Resolve {
+                path: Some(path),
+                raise_not_found,
+            };
+            // Fails only if the loop was closed in the meantime, then nobody is waiting for the result
+            let _ = Py::new(py, resolve).and_then(|resolve| {
+                event_loop.call_method1(py, "call_soon_threadsafe", (resolve, handle))
+            });
+        });
+    });
+    Ok(result)
+}